@@ -2,7 +2,7 @@
 # Tychee Contracts Deployment Script
 # 
 # This script deploys Soroban smart contracts for the Tychee platform.
-# - Account Abstraction: Deployed once as shared infrastructure
+# - Account Abstraction: Standalone instance; user wallets come from the Wallet Factory
 # - Token Vault: Deployed per-SDK implementation
 #
 # Usage:
@@ -103,8 +103,12 @@ deploy_account_abstraction() {
     
     echo -e "${GREEN}✅ Account Abstraction deployed: $AA_CONTRACT_ID${NC}"
     
-    # Initialize
+    # Initialize with native XLM as the gas token
     echo -e "${BLUE}Initializing Account Abstraction...${NC}"
+    GAS_TOKEN_ID=$(stellar contract id asset \
+        --asset native \
+        --network "$NETWORK")
+    
     stellar contract invoke \
         --id "$AA_CONTRACT_ID" \
         --source "$KEY_NAME" \
@@ -112,9 +116,22 @@ deploy_account_abstraction() {
         -- \
         initialize \
         --owner "$DEPLOYER_ADDRESS" \
-        --initial_gas_pool "$GAS_POOL_AMOUNT"
+        --gas_token "$GAS_TOKEN_ID"
+    
+    echo -e "${GREEN}✅ Account Abstraction initialized with gas token: $GAS_TOKEN_ID${NC}"
+    
+    # Fund the deployer's sponsor pool
+    echo -e "${BLUE}Funding gas pool...${NC}"
+    stellar contract invoke \
+        --id "$AA_CONTRACT_ID" \
+        --source "$KEY_NAME" \
+        --network "$NETWORK" \
+        -- \
+        fund_gas_pool \
+        --sponsor "$DEPLOYER_ADDRESS" \
+        --amount "$GAS_POOL_AMOUNT"
     
-    echo -e "${GREEN}✅ Account Abstraction initialized with gas pool: $GAS_POOL_AMOUNT stroops${NC}"
+    echo -e "${GREEN}✅ Gas pool funded: $GAS_POOL_AMOUNT stroops${NC}"
     
     # Save to file
     echo "$AA_CONTRACT_ID" > "$PROJECT_ROOT/.aa-contract-id-$NETWORK"
@@ -158,7 +175,7 @@ deploy_token_vault() {
     echo "$VAULT_CONTRACT_ID" > "$PROJECT_ROOT/.vault-contract-id-$NETWORK"
     echo ""
     
-    # Setup AA mode if AA contract exists
+    # Configure Account Abstraction if the AA contract exists
     if [ -f "$PROJECT_ROOT/.aa-contract-id-$NETWORK" ]; then
         AA_CONTRACT_ID=$(cat "$PROJECT_ROOT/.aa-contract-id-$NETWORK")
         
@@ -168,11 +185,13 @@ deploy_token_vault() {
             --source "$KEY_NAME" \
             --network "$NETWORK" \
             -- \
-            set_mode \
-            --user "$DEPLOYER_ADDRESS" \
-            --mode '"Sponsored"'
+            set_token_vault \
+            --vault "$VAULT_CONTRACT_ID"
         
-        echo -e "${GREEN}✅ AA mode set to Sponsored${NC}"
+        echo -e "${GREEN}✅ Token Vault reads use the AA Read threshold${NC}"
+        # set_sponsor needs the account's own authorization (its owner signing through
+        # __check_auth), so sponsorship is set up from the wallet rather than here
+        echo -e "${YELLOW}ℹ️  Sponsor the AA account with set_sponsor from the wallet${NC}"
     fi
     
     export VAULT_CONTRACT_ID
@@ -3,15 +3,22 @@
  * Main entry point for card tokenization SDK
  */
 
-import { Horizon, Keypair, Networks, Contract, TransactionBuilder, Address, nativeToScVal, BASE_FEE } from '@stellar/stellar-sdk';
-import { TycheeConfig, CardData, TokenMetadata, TransactionResult } from '../types';
+import { Horizon, Keypair, Networks, Contract, TransactionBuilder, Address, StrKey, hash, nativeToScVal, xdr, BASE_FEE } from '@stellar/stellar-sdk';
+import { TycheeConfig, CardData, TokenMetadata, TransactionResult, AAFeature, AAFeatures } from '../types';
 import { CardTokenizer, RingCompatibleCrypto } from '../crypto';
 
+/** AA contract `Feature` variant for each SDK feature name */
+const AA_FEATURE_VARIANTS: Record<AAFeature, string> = {
+    sponsored: 'Sponsored',
+    sessionKeys: 'SessionKeys',
+    multisig: 'MultiSig',
+    paymaster: 'Paymaster',
+};
+
 export class TycheeSDK {
     private config: TycheeConfig;
     private server: Horizon.Server;
     private contract: Contract;
-    private aaContract?: Contract;
     private userKeypair?: Keypair;
 
     constructor(config: TycheeConfig) {
@@ -22,10 +29,6 @@ export class TycheeSDK {
 
         // Initialize contracts
         this.contract = new Contract(config.tokenVaultAddress);
-
-        if (config.useAccountAbstraction && config.accountAbstractionAddress) {
-            this.aaContract = new Contract(config.accountAbstractionAddress);
-        }
     }
 
     /**
@@ -54,6 +57,37 @@ export class TycheeSDK {
         return this.userKeypair.publicKey();
     }
 
+    /**
+     * Get the address of the user's AA wallet, deployed (or yet to be deployed) by the Wallet Factory
+     */
+    getWalletAddress(): string {
+        if (!this.userKeypair) {
+            throw new Error('SDK not initialized. Call initialize() first.');
+        }
+        if (!this.config.walletFactoryAddress) {
+            throw new Error('Wallet factory address not configured');
+        }
+
+        // Same derivation as the factory's predict_wallet: salt is sha256 of the owner's address XDR
+        const salt = hash(new Address(this.userKeypair.publicKey()).toScVal().toXDR());
+        const networkPassphrase = this.config.stellarNetwork === 'testnet'
+            ? Networks.TESTNET
+            : Networks.PUBLIC;
+        const preimage = xdr.HashIdPreimage.envelopeTypeContractId(
+            new xdr.HashIdPreimageContractId({
+                networkId: hash(Buffer.from(networkPassphrase)),
+                contractIdPreimage: xdr.ContractIdPreimage.contractIdPreimageFromAddress(
+                    new xdr.ContractIdPreimageFromAddress({
+                        address: new Address(this.config.walletFactoryAddress).toScAddress(),
+                        salt,
+                    })
+                ),
+            })
+        );
+
+        return StrKey.encodeContract(hash(preimage.toXDR()));
+    }
+
     /**
      * Tokenize and store a card on-chain
      */
@@ -104,6 +138,7 @@ export class TycheeSDK {
         const last4 = nativeToScVal(last4Digits, { type: 'string' });
         const network = nativeToScVal(cardData.network, { type: 'string' });
         const expiresAtVal = nativeToScVal(expiresAt, { type: 'u64' });
+        const attestation = nativeToScVal(null); // No issuer attestation
 
         try {
             // Build and submit transaction
@@ -123,7 +158,8 @@ export class TycheeSDK {
                         hashBytes,
                         last4,
                         network,
-                        expiresAtVal
+                        expiresAtVal,
+                        attestation
                     )
                 )
                 .setTimeout(30)
@@ -156,9 +192,9 @@ export class TycheeSDK {
     }
 
     /**
-     * Retrieve encrypted card token from on-chain
+     * Retrieve an encrypted card token from on-chain by its hex token hash
      */
-    async retrieveCard(): Promise<TokenMetadata | null> {
+    async retrieveCard(tokenHash: string): Promise<TokenMetadata | null> {
         if (!this.userKeypair) {
             throw new Error('SDK not initialized');
         }
@@ -175,7 +211,11 @@ export class TycheeSDK {
                     : Networks.PUBLIC,
             })
                 .addOperation(
-                    this.contract.call('retrieve_token', userAddress.toScVal())
+                    this.contract.call(
+                        'retrieve_token',
+                        userAddress.toScVal(),
+                        nativeToScVal(Buffer.from(tokenHash, 'hex'), { type: 'bytes' })
+                    )
                 )
                 .setTimeout(30)
                 .build();
@@ -196,9 +236,9 @@ export class TycheeSDK {
     }
 
     /**
-     * Revoke card token
+     * Revoke a card token by its hex token hash
      */
-    async revokeCard(): Promise<TransactionResult> {
+    async revokeCard(tokenHash: string): Promise<TransactionResult> {
         if (!this.userKeypair) {
             throw new Error('SDK not initialized');
         }
@@ -215,7 +255,11 @@ export class TycheeSDK {
                     : Networks.PUBLIC,
             })
                 .addOperation(
-                    this.contract.call('revoke_token', userAddress.toScVal())
+                    this.contract.call(
+                        'revoke_token',
+                        userAddress.toScVal(),
+                        nativeToScVal(Buffer.from(tokenHash, 'hex'), { type: 'bytes' })
+                    )
                 )
                 .setTimeout(30)
                 .build();
@@ -254,15 +298,18 @@ export class TycheeSDK {
     }
 
     /**
-     * Get account abstraction mode
+     * Get the account abstraction features active on the user's wallet
      */
-    async getAAMode(): Promise<string> {
-        if (!this.config.useAccountAbstraction || !this.aaContract || !this.userKeypair) {
-            return 'standard';
+    async getAAFeatures(): Promise<AAFeatures> {
+        const none: AAFeatures = { sponsored: false, sessionKeys: false, multisig: false, paymaster: false };
+        if (!this.config.useAccountAbstraction || !this.config.walletFactoryAddress || !this.userKeypair) {
+            return none;
         }
 
         try {
-            const userAddress = new Address(this.userKeypair.publicKey());
+            // Wallets only accept feature calls for their own address
+            const walletAddress = this.getWalletAddress();
+            const wallet = new Contract(walletAddress);
             const account = await this.server.loadAccount(this.userKeypair.publicKey());
 
             const transaction = new TransactionBuilder(account, {
@@ -272,7 +319,7 @@ export class TycheeSDK {
                     : Networks.PUBLIC,
             })
                 .addOperation(
-                    this.aaContract.call('get_mode', userAddress.toScVal())
+                    wallet.call('get_features', new Address(walletAddress).toScVal())
                 )
                 .setTimeout(30)
                 .build();
@@ -280,25 +327,29 @@ export class TycheeSDK {
             transaction.sign(this.userKeypair);
             const response = await this.server.submitTransaction(transaction);
 
-            // Parse mode from response
-            return 'standard'; // Placeholder
+            // Parse features from response
+            return none; // Placeholder
         } catch (error) {
-            console.error('Error getting AA mode:', error);
-            return 'standard';
+            console.error('Error getting AA features:', error);
+            return none;
         }
     }
 
     /**
-     * Set account abstraction mode
+     * Enable or disable one account abstraction feature on the user's wallet, leaving the others untouched
      */
-    async setAAMode(mode: 'standard' | 'sponsored' | 'sessionKey' | 'multisig'): Promise<TransactionResult> {
-        if (!this.config.useAccountAbstraction || !this.aaContract || !this.userKeypair) {
+    async setAAFeature(feature: AAFeature, enabled: boolean): Promise<TransactionResult> {
+        if (!this.config.useAccountAbstraction || !this.config.walletFactoryAddress || !this.userKeypair) {
             throw new Error('Account abstraction not enabled');
         }
 
         try {
-            const userAddress = new Address(this.userKeypair.publicKey());
-            const modeVal = nativeToScVal(mode, { type: 'string' });
+            // Wallets only accept feature calls for their own address
+            const walletAddress = this.getWalletAddress();
+            const wallet = new Contract(walletAddress);
+            // Contract enum variant: vec![Symbol(variant)]
+            const featureVal = xdr.ScVal.scvVec([xdr.ScVal.scvSymbol(AA_FEATURE_VARIANTS[feature])]);
+            const enabledVal = nativeToScVal(enabled, { type: 'bool' });
 
             const account = await this.server.loadAccount(this.userKeypair.publicKey());
 
@@ -309,7 +360,7 @@ export class TycheeSDK {
                     : Networks.PUBLIC,
             })
                 .addOperation(
-                    this.aaContract.call('set_mode', userAddress.toScVal(), modeVal)
+                    wallet.call('set_feature', new Address(walletAddress).toScVal(), featureVal, enabledVal)
                 )
                 .setTimeout(30)
                 .build();
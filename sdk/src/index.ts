@@ -9,6 +9,8 @@ export type {
     CardData,
     TokenMetadata,
     TransactionResult,
+    AAFeature,
+    AAFeatures,
     ZKProof
 } from './types';
 
@@ -9,7 +9,7 @@ export interface TycheeConfig {
 
     // Contract Addresses
     tokenVaultAddress: string;
-    accountAbstractionAddress?: string;
+    walletFactoryAddress?: string;  // Deploys each user's AA wallet
 
     // Account Abstraction
     useAccountAbstraction: boolean;
@@ -52,6 +52,21 @@ export interface TokenMetadata {
     sorobanTxId?: string;
 }
 
+/**
+ * Account Abstraction feature, toggled independently of the others
+ */
+export type AAFeature = 'sponsored' | 'sessionKeys' | 'multisig' | 'paymaster';
+
+/**
+ * Account Abstraction features active for a user
+ */
+export interface AAFeatures {
+    sponsored: boolean;
+    sessionKeys: boolean;
+    multisig: boolean;
+    paymaster: boolean;
+}
+
 /**
  * Transaction Result
  */
@@ -1,35 +1,367 @@
 #![no_std]
-use soroban_sdk::{contract, contractimpl, contracttype, symbol_short, Address, Bytes, BytesN, Env, String, Vec};
+// execute_metatx/execute_batch take 10/8 arguments; the `AccountAbstractionArgs` helpers
+// #[contractimpl] generates for them are emitted outside the impl without the function's attributes,
+// so a function-level allow does not reach them
+#![allow(clippy::too_many_arguments)]
+use soroban_sdk::{
+    auth::{Context, CustomAccountInterface},
+    contract, contracterror, contractimpl, contracttype,
+    crypto::Hash,
+    symbol_short, token, vec,
+    xdr::ToXdr,
+    Address, Bytes, BytesN, Env, IntoVal, InvokeError, Map, Symbol, Topics, TryFromVal, Val, Vec,
+};
 
-/// Account abstraction modes
+/// Maximum number of active session keys per user
+const MAX_SESSION_KEYS: u32 = 10;
+
+/// Fee multiplier denominator (10_000 = 1x)
+const FEE_MULTIPLIER_BASE: u32 = 10_000;
+
+/// Paymaster exchange rate denominator (fee token units per gas token unit, 7 decimals)
+const RATE_SCALE: i128 = 10_000_000;
+
+/// Largest WebAuthn clientDataJSON accepted from a passkey
+const MAX_CLIENT_DATA_LEN: usize = 1024;
+
+/// The configured TokenVault's only read that requires auth, authorized at the `Read` multisig threshold
+const VAULT_READ_FUNCTION: &str = "retrieve_token";
+
+/// Account abstraction features; any combination can be active at once
 #[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub enum AAMode {
-    Standard,       // No AA - user pays gas
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Feature {
     Sponsored,      // Gas sponsored by sponsor
-    SessionKey,     // Temporary session key
-    MultiSig,       // Multi-signature required
+    SessionKeys,    // Temporary scoped session keys
+    MultiSig,       // Multi-signature required for the account's own auth
+    Paymaster,      // User repays the sponsor in another token
+}
+
+/// Active AA features for an account (all off = standard account, user pays gas)
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AccountFeatures {
+    pub sponsored: bool,
+    pub session_keys: bool,
+    pub multisig: bool,
+    pub paymaster: bool,
 }
 
 /// Storage keys
 #[contracttype]
 pub enum DataKey {
-    Mode(Address),          // User -> AA mode
+    Features(Address),      // User -> active AA features
     Sponsor(Address),       // User -> sponsor address
-    SessionKey(Address),    // User -> session keys
-    Signers(Address),       // User -> multi-sig signers
-    Threshold(Address),     // User -> multi-sig threshold
-    GasPool,                // Total gas pool for sponsorship
+    SessionKey(Address, BytesN<32>), // (User, public key) -> session key
+    SessionKeys(Address),   // User -> registered session public keys
+    Signers(Address),       // User -> multi-sig signer weights
+    Threshold(Address),     // User -> multi-sig weight threshold (Standard class)
+    ClassThreshold(Address, OperationClass), // (User, class) -> weight threshold override
+    GasPool,                // Sum of all sponsor pool balances
+    GasToken,               // Stellar Asset Contract the gas pool is denominated in
+    SponsorPool(Address),   // Sponsor -> deposited balance and spend
+    SponsorPolicy(Address), // User -> limits set by the user's sponsor
+    SponsorUsage(Address),  // User -> spend and quota usage under the current sponsor
     Owner,                  // Contract owner
+    Guardian,               // Address allowed to pause alongside the owner
+    Paused,                 // Emergency pause flag
+    WalletMode,             // Per-user wallet: account state only for the contract's own address
+    FeeAdmin,               // Sets a wallet's fee schedule and rates instead of its owner
+    TokenVault,             // TokenVault whose retrieve_token calls use the Read class threshold
+    FeeSchedule,            // Meta-tx fee schedule set by the owner
+    FeeTokenRate(Address),  // Paymaster token -> units per gas token unit (RATE_SCALE)
+    FeePayment(Address),    // User -> paymaster token
+    Nonce(Address),         // User -> next meta-transaction / session payload nonce
+    NextProposalId,         // Next multisig proposal id
+    Proposal(u64),          // Proposal id -> multisig proposal
 }
 
 /// Session key data
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct SessionKey {
-    pub key: BytesN<32>,
+    pub key: BytesN<32>,           // ed25519 public key, or SHA-256 of the passkey
+    pub expires_at: u64,
+    pub permissions: Vec<SessionScope>,  // Calls the key may authorize
+    pub passkey: Option<BytesN<65>>,     // secp256r1 public key for passkey session keys
+}
+
+/// Call a session key is allowed to authorize
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SessionScope {
+    pub contract: Address,
+    pub function: Symbol,
+    pub arg_limits: Vec<ArgLimit>,  // Constraints on i128 arguments, all must hold
+}
+
+/// Upper bound on an i128 call argument (e.g. a token amount)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ArgLimit {
+    pub index: u32,                // Argument position
+    pub max: i128,
+}
+
+/// Session key authorization for a meta-transaction
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SessionAuth {
+    pub public_key: BytesN<32>,
+    pub signature: BytesN<64>,     // Signature over the call's `SessionPayload`
+}
+
+/// Gas pool funded by a single sponsor
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SponsorPool {
+    pub balance: i128,             // Available for meta-tx costs
+    pub spent: i128,               // Total charged for sponsored meta-txs
+}
+
+/// Limits a sponsor places on a sponsored user; `None` means unlimited
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SponsorPolicy {
+    pub max_spend: Option<i128>,   // Lifetime spend cap for this user
+    pub tx_quota: Option<u32>,     // Meta-txs allowed per period
+    pub period: u64,               // Quota period in seconds (e.g. 86400 for tx/day)
+    pub allowed_targets: Vec<Address>, // Empty allows any target
+    pub expires_at: Option<u64>,   // Sponsorship ends after this timestamp
+}
+
+/// Usage counted against a sponsor policy
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SponsorUsage {
+    pub spent: i128,
+    pub period_start: u64,
+    pub period_tx_count: u32,
+}
+
+/// Remaining sponsorship for a user; `None` means unlimited
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SponsorAllowance {
+    pub sponsor: Address,
+    pub remaining_spend: Option<i128>,
+    pub remaining_tx: Option<u32>, // In the current period
+    pub expires_at: Option<u64>,
+}
+
+/// Gas pool accounting compared with the contract's actual token balance
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolReconciliation {
+    pub accounted: i128,           // Sum of sponsor balances
+    pub balance: i128,             // Gas token held by the contract
+    pub surplus: i128,             // balance - accounted; negative if the pools are underfunded
+}
+
+/// Call description signed by a session key
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SessionPayload {
+    pub user: Address,             // Account the call is made for
+    pub verifier: Address,         // AccountAbstraction contract the signature is meant for
+    pub contract: Address,         // Target contract
+    pub function: Symbol,          // Target function
+    pub args_hash: BytesN<32>,     // SHA-256 of the XDR-encoded call arguments
+    pub max_fee: i128,             // Most the user pays for the meta-tx
+    pub nonce: u64,                // Must equal the user's current nonce
+    pub expires_at: u64,           // Signature is invalid after this timestamp
+}
+
+/// Meta-tx fee schedule (gas token units)
+/// fee = network_fee + base_fee + sum over calls of
+///       (call_fee + per_byte_fee * arg XDR bytes) * target multiplier / 10_000
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeSchedule {
+    pub base_fee: i128,            // Once per meta-tx or batch
+    pub call_fee: i128,            // Per dispatched call
+    pub per_byte_fee: i128,        // Per byte of XDR-encoded call arguments
+    pub target_multipliers: Map<Address, u32>,  // Target -> multiplier (10_000 = 1x, default)
+    pub max_network_fee: i128,     // Cap on the network fee a relayer may claim
+}
+
+/// Fee breakdown emitted as a receipt for every meta-tx
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeReceipt {
+    pub sponsor: Address,
+    pub relayer: Address,
+    pub network_fee: i128,         // Reported by the relayer, within the cap
+    pub base_fee: i128,
+    pub execution_fee: i128,       // Per-call and per-byte fees after multipliers
+    pub total: i128,               // Charged to the sponsor, paid to the relayer
+}
+
+/// Paymaster settings: the account repays each meta-tx fee to the sponsor in `token`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeePayment {
+    pub token: Address,            // Stellar Asset Contract with an owner-set rate
+}
+
+/// One call in a batched meta-transaction
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Call {
+    pub contract: Address,
+    pub function: Symbol,
+    pub args: Vec<Val>,
+}
+
+/// Payload a session key signs to authorize a batch
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BatchPayload {
+    pub user: Address,             // Account the batch is made for
+    pub verifier: Address,         // AccountAbstraction contract the signature is meant for
+    pub calls_hash: BytesN<32>,    // SHA-256 of the XDR-encoded calls
+    pub max_fee: i128,             // Most the user pays for the batch
+    pub nonce: u64,                // Must equal the user's current nonce
+    pub expires_at: u64,           // Signature is invalid after this timestamp
+}
+
+/// Multisig operation classes, each with its own weight threshold
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OperationClass {
+    Read,           // TokenVault reads
+    Standard,       // Everything else
+    Admin,          // Signer and threshold changes
+}
+
+/// Multisig signer
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Signer {
+    Address(Address),
+    Passkey(BytesN<65>),           // Uncompressed secp256r1 public key
+}
+
+/// Multisig configuration change approved by the current signers
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SignerChange {
+    Add(Signer, u32),              // (signer, weight)
+    Remove(Signer),
+    ChangeThreshold(u32),
+    Replace(Signer, Signer),       // (old signer, new signer), weight carries over
+    SetWeight(Signer, u32),
+    SetClassThreshold(OperationClass, u32),
+}
+
+/// Multisig proposal lifecycle
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProposalStatus {
+    Pending,
+    Executed,
+    Cancelled,
+}
+
+/// Call proposed by a signer and approved asynchronously by the others
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Proposal {
+    pub id: u64,
+    pub user: Address,             // Multisig account the proposal belongs to
+    pub proposer: Signer,
+    pub target: Address,
+    pub function: Symbol,
+    pub args: Vec<Val>,
+    pub approvals: Vec<Signer>,    // Signers that approved, proposer included
     pub expires_at: u64,
-    pub permissions: Vec<String>,  // List of allowed operations
+    pub status: ProposalStatus,
+}
+
+/// Signature accepted by `__check_auth` when the contract is deployed as a smart wallet
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AccountSignature {
+    Owner,                          // Owner address authorizes the payload
+    SessionKey(SessionSignature),   // Session key signs the payload
+    Passkey(WebAuthnSignature),     // Passkey session key signs the payload
+    MultiSig(Vec<SignerProof>),     // Signers authorizing the payload
+}
+
+/// One multisig signer's approval; passkeys sign the challenge described at `require_proof`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum SignerProof {
+    Address(Address),               // Address authorizes the payload
+    Passkey(WebAuthnSignature),
+}
+
+/// WebAuthn assertion; the clientDataJSON challenge is the base64url payload
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WebAuthnSignature {
+    pub public_key: BytesN<65>,
+    pub authenticator_data: Bytes,
+    pub client_data_json: Bytes,
+    pub signature: BytesN<64>,      // Low-S (r, s) over SHA-256(authenticatorData || SHA-256(clientDataJSON))
+}
+
+/// ed25519 signature made with a registered session key
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SessionSignature {
+    pub public_key: BytesN<32>,
+    pub signature: BytesN<64>,
+}
+
+/// Account abstraction errors
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum AAError {
+    NotInitialized = 1,
+    SignatureNotAllowed = 2,    // Signature kind not accepted with the account's features
+    InvalidSessionKey = 3,      // Unknown or expired session key
+    UnknownSigner = 4,
+    DuplicateSigner = 5,
+    ThresholdNotMet = 6,
+    SignatureExpired = 7,
+    InvalidNonce = 8,
+    OutOfScope = 9,             // Call not covered by the session key's permissions
+    SponsorshipExpired = 10,
+    SpendLimitExceeded = 11,    // Sponsor's per-user spend cap reached
+    TxQuotaExceeded = 12,       // Sponsor's per-period meta-tx quota reached
+    TargetNotAllowed = 13,      // Target not on the sponsor's allow list
+    ProposalExpired = 14,
+    InvalidWebAuthn = 15,       // Malformed authenticator data or client data / challenge mismatch
+    FeeAboveCap = 16,           // Relayer's network fee exceeds the schedule's cap
+    UnsupportedFeeToken = 17,   // No exchange rate set for the paymaster token
+    SlippageExceeded = 18,      // Fee above the max_fee the user signed
+    AlreadyInitialized = 19,
+    Paused = 20,                // Meta-transactions and policy changes are blocked
+    NotAuthorized = 21,         // Caller is neither the owner nor the guardian
+    NoSponsor = 22,
+    SponsorshipNotEnabled = 23,
+    MultisigNotConfigured = 24,
+    MultisigAlreadyConfigured = 25,
+    PaymasterNotConfigured = 26,
+    InvalidAmount = 27,         // Non-positive amount or rate, or more than the available balance
+    InvalidFeeSchedule = 28,    // Negative fee component
+    InvalidQuotaPeriod = 29,    // tx_quota set with a zero period
+    InvalidThreshold = 30,      // Zero or above the total signer weight
+    InvalidSignerWeight = 31,
+    SignerExists = 32,
+    TooManySessionKeys = 33,
+    ProposalNotFound = 34,
+    ProposalNotPending = 35,
+    EmptyBatch = 36,
+    CallFailed = 37,            // A dispatched meta-tx call failed
+    InsufficientGasPool = 38,   // Sponsor's pool cannot cover the fee
+    PayloadMismatch = 39,       // Signed payload is bound to another user or contract
+    NotOwnAccount = 40,         // Calls are only dispatched for the contract's own account
+    ReservedTarget = 41,        // The gas token and the contract cannot be called as the account
+    GasTokenNotAllowed = 42,    // The gas token backs the sponsor pools and cannot be a fee token
 }
 
 #[contract]
@@ -38,218 +370,1635 @@ pub struct AccountAbstraction;
 #[contractimpl]
 impl AccountAbstraction {
     /// Initialize the contract
-    pub fn initialize(env: Env, owner: Address, initial_gas_pool: i128) {
+    /// gas_token: Stellar Asset Contract (e.g. XLM or USDC) backing the gas pools
+    pub fn initialize(env: Env, owner: Address, gas_token: Address) -> Result<(), AAError> {
         if env.storage().instance().has(&DataKey::Owner) {
-            panic!("Already initialized");
+            return Err(AAError::AlreadyInitialized);
         }
-        
+
         owner.require_auth();
         env.storage().instance().set(&DataKey::Owner, &owner);
-        env.storage().instance().set(&DataKey::GasPool, &initial_gas_pool);
+        env.storage().instance().set(&DataKey::GasToken, &gas_token);
+        env.storage().instance().set(&DataKey::GasPool, &0i128);
+        Ok(())
     }
 
-    /// Set AA mode for user
-    pub fn set_mode(env: Env, user: Address, mode: AAMode) {
-        user.require_auth();
-        env.storage().persistent().set(&DataKey::Mode(user.clone()), &mode);
-        
-        env.events().publish(
-            (symbol_short!("aa_mode"), user),
-            mode
+    /// Initialize as a per-user wallet (used by the WalletFactory); account features,
+    /// sponsorship, session keys, signers and fee payment can then only be set for this
+    /// contract's own address. fee_admin, not the owner, sets the fee schedule and rates,
+    /// since the owner could otherwise charge its sponsor whatever it relays for itself
+    pub fn initialize_wallet(env: Env, owner: Address, gas_token: Address, fee_admin: Address) -> Result<(), AAError> {
+        Self::initialize(env.clone(), owner, gas_token)?;
+        env.storage().instance().set(&DataKey::WalletMode, &true);
+        env.storage().instance().set(&DataKey::FeeAdmin, &fee_admin);
+        Ok(())
+    }
+
+    /// Check if the contract is a per-user wallet
+    pub fn is_wallet(env: Env) -> bool {
+        env.storage().instance().get(&DataKey::WalletMode).unwrap_or(false)
+    }
+
+    /// Set or clear the guardian allowed to pause the contract (owner only)
+    pub fn set_guardian(env: Env, guardian: Option<Address>) -> Result<(), AAError> {
+        let owner = Self::owner(&env)?;
+        owner.require_auth();
+
+        match &guardian {
+            Some(guardian) => env.storage().instance().set(&DataKey::Guardian, guardian),
+            None => env.storage().instance().remove(&DataKey::Guardian),
+        }
+
+        publish(&env,
+            (symbol_short!("guardian"), owner),
+            guardian
+        );
+        Ok(())
+    }
+
+    /// Get the guardian, if any
+    pub fn get_guardian(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::Guardian)
+    }
+
+    /// Set or clear the TokenVault whose retrieve_token calls fall in the Read class (owner only)
+    pub fn set_token_vault(env: Env, vault: Option<Address>) -> Result<(), AAError> {
+        let owner = Self::owner(&env)?;
+        owner.require_auth();
+
+        match &vault {
+            Some(vault) => env.storage().instance().set(&DataKey::TokenVault, vault),
+            None => env.storage().instance().remove(&DataKey::TokenVault),
+        }
+
+        publish(&env,
+            (symbol_short!("vault"), owner),
+            vault
+        );
+        Ok(())
+    }
+
+    /// Get the TokenVault reads are classified against, if any
+    pub fn get_token_vault(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::TokenVault)
+    }
+
+    /// Emergency pause (owner or guardian) - blocks meta-transactions and policy changes;
+    /// revocations and gas pool withdrawals stay available
+    pub fn pause(env: Env, caller: Address) -> Result<(), AAError> {
+        caller.require_auth();
+
+        if caller != Self::owner(&env)? && Self::get_guardian(env.clone()) != Some(caller.clone()) {
+            return Err(AAError::NotAuthorized);
+        }
+
+        env.storage().instance().set(&DataKey::Paused, &true);
+
+        publish(&env,
+            (symbol_short!("pause"), caller),
+            env.ledger().timestamp()
+        );
+        Ok(())
+    }
+
+    /// Lift the emergency pause (owner only)
+    pub fn unpause(env: Env) -> Result<(), AAError> {
+        let owner = Self::owner(&env)?;
+        owner.require_auth();
+
+        env.storage().instance().set(&DataKey::Paused, &false);
+
+        publish(&env,
+            (symbol_short!("unpause"), owner),
+            env.ledger().timestamp()
         );
+        Ok(())
+    }
+
+    /// Check if contract is paused
+    pub fn is_paused(env: Env) -> bool {
+        env.storage().instance().get(&DataKey::Paused).unwrap_or(false)
+    }
+
+    /// Enable or disable one AA feature for user, leaving the others untouched
+    pub fn set_feature(env: Env, user: Address, feature: Feature, enabled: bool) -> Result<(), AAError> {
+        user.require_auth();
+        Self::require_not_paused(&env)?;
+        Self::require_account(&env, &user)?;
+
+        if enabled {
+            match feature {
+                Feature::Sponsored => {
+                    Self::sponsor_of(&env, &user)?;
+                },
+                Feature::MultiSig => {
+                    if !env.storage().persistent().has(&DataKey::Signers(user.clone())) {
+                        return Err(AAError::MultisigNotConfigured);
+                    }
+                },
+                Feature::Paymaster => {
+                    if !env.storage().persistent().has(&DataKey::FeePayment(user.clone())) {
+                        return Err(AAError::PaymasterNotConfigured);
+                    }
+                },
+                Feature::SessionKeys => {},
+            }
+        }
+
+        Self::update_feature(&env, &user, feature, enabled);
+        Ok(())
     }
 
-    /// Get AA mode for user
-    pub fn get_mode(env: Env, user: Address) -> AAMode {
+    /// Get the AA features active for user
+    pub fn get_features(env: Env, user: Address) -> AccountFeatures {
         env.storage().persistent()
-            .get(&DataKey::Mode(user))
-            .unwrap_or(AAMode::Standard)
+            .get(&DataKey::Features(user))
+            .unwrap_or_default()
     }
 
-    /// Set gas sponsor for user
-    pub fn set_sponsor(env: Env, user: Address, sponsor: Address) {
+    /// Set gas sponsor for user; both authorize (unlimited until the sponsor sets a policy)
+    pub fn set_sponsor(env: Env, user: Address, sponsor: Address) -> Result<(), AAError> {
+        user.require_auth();
         sponsor.require_auth();
-        
+        Self::require_not_paused(&env)?;
+        Self::require_account(&env, &user)?;
+
         env.storage().persistent().set(&DataKey::Sponsor(user.clone()), &sponsor);
-        env.storage().persistent().set(&DataKey::Mode(user.clone()), &AAMode::Sponsored);
-        
-        env.events().publish(
+        env.storage().persistent().remove(&DataKey::SponsorPolicy(user.clone()));
+        env.storage().persistent().remove(&DataKey::SponsorUsage(user.clone()));
+        Self::update_feature(&env, &user, Feature::Sponsored, true);
+
+        publish(&env,
             (symbol_short!("sponsor"), user),
             sponsor
         );
+        Ok(())
+    }
+
+    /// Set the limits on a sponsored user (current sponsor only)
+    pub fn set_sponsor_policy(env: Env, user: Address, policy: SponsorPolicy) -> Result<(), AAError> {
+        let sponsor = Self::sponsor_of(&env, &user)?;
+        sponsor.require_auth();
+        Self::require_not_paused(&env)?;
+
+        if policy.tx_quota.is_some() && policy.period == 0 {
+            return Err(AAError::InvalidQuotaPeriod);
+        }
+
+        env.storage().persistent().set(&DataKey::SponsorPolicy(user.clone()), &policy);
+
+        publish(&env,
+            (symbol_short!("sp_policy"), user),
+            sponsor
+        );
+        Ok(())
+    }
+
+    /// Stop sponsoring a user (current sponsor only)
+    pub fn revoke_sponsorship(env: Env, user: Address) -> Result<(), AAError> {
+        let sponsor = Self::sponsor_of(&env, &user)?;
+        sponsor.require_auth();
+
+        env.storage().persistent().remove(&DataKey::Sponsor(user.clone()));
+        env.storage().persistent().remove(&DataKey::SponsorPolicy(user.clone()));
+        env.storage().persistent().remove(&DataKey::SponsorUsage(user.clone()));
+        Self::update_feature(&env, &user, Feature::Sponsored, false);
+
+        publish(&env,
+            (symbol_short!("sp_revoke"), user),
+            sponsor
+        );
+        Ok(())
+    }
+
+    /// Get the user's remaining sponsorship, if sponsored
+    pub fn get_sponsor_allowance(env: Env, user: Address) -> Option<SponsorAllowance> {
+        let sponsor: Address = env.storage().persistent().get(&DataKey::Sponsor(user.clone()))?;
+        let policy: Option<SponsorPolicy> = env.storage().persistent().get(&DataKey::SponsorPolicy(user.clone()));
+        let usage = Self::current_usage(&env, &user, policy.as_ref());
+
+        Some(match policy {
+            Some(policy) => SponsorAllowance {
+                sponsor,
+                remaining_spend: policy.max_spend.map(|max| (max - usage.spent).max(0)),
+                remaining_tx: policy.tx_quota.map(|quota| quota.saturating_sub(usage.period_tx_count)),
+                expires_at: policy.expires_at,
+            },
+            None => SponsorAllowance {
+                sponsor,
+                remaining_spend: None,
+                remaining_tx: None,
+                expires_at: None,
+            },
+        })
     }
 
-    /// Add session key (temporary key for gasless tx)
+    /// Add session key (temporary ed25519 public key for gasless tx)
     pub fn add_session_key(
         env: Env,
         user: Address,
         session_key: BytesN<32>,
         duration: u64,
-        permissions: Vec<String>,
-    ) {
+        permissions: Vec<SessionScope>,
+    ) -> Result<(), AAError> {
         user.require_auth();
-        
-        let expires_at = env.ledger().timestamp() + duration;
-        
-        let key_data = SessionKey {
-            key: session_key,
-            expires_at,
-            permissions,
-        };
-        
-        // Store session key
-        let mut session_keys: Vec<SessionKey> = env.storage().persistent()
-            .get(&DataKey::SessionKey(user.clone()))
-            .unwrap_or(Vec::new(&env));
-        
-        session_keys.push_back(key_data);
-        env.storage().persistent().set(&DataKey::SessionKey(user.clone()), &session_keys);
-        
-        // Update mode
-        env.storage().persistent().set(&DataKey::Mode(user.clone()), &AAMode::SessionKey);
-        
-        env.events().publish(
-            (symbol_short!("session"), user),
-            expires_at
+        Self::require_not_paused(&env)?;
+        Self::require_account(&env, &user)?;
+        Self::register_session_key(&env, &user, session_key, None, duration, permissions)
+    }
+
+    /// Add a passkey (secp256r1) session key; it is listed and revoked by the SHA-256 of its public key
+    pub fn add_passkey_session_key(
+        env: Env,
+        user: Address,
+        public_key: BytesN<65>,
+        duration: u64,
+        permissions: Vec<SessionScope>,
+    ) -> Result<BytesN<32>, AAError> {
+        user.require_auth();
+        Self::require_not_paused(&env)?;
+        Self::require_account(&env, &user)?;
+
+        let key_id: BytesN<32> = env.crypto().sha256(&public_key.clone().into()).into();
+        Self::register_session_key(&env, &user, key_id.clone(), Some(public_key), duration, permissions)?;
+        Ok(key_id)
+    }
+
+    /// Revoke a session key
+    pub fn revoke_session_key(env: Env, user: Address, session_key: BytesN<32>) -> Result<(), AAError> {
+        user.require_auth();
+
+        let mut session_keys = Self::prune_session_keys(&env, &user);
+        let index = session_keys.first_index_of(&session_key).ok_or(AAError::InvalidSessionKey)?;
+        session_keys.remove(index);
+
+        env.storage().persistent().remove(&DataKey::SessionKey(user.clone(), session_key.clone()));
+        env.storage().persistent().set(&DataKey::SessionKeys(user.clone()), &session_keys);
+
+        publish(&env,
+            (symbol_short!("sk_revoke"), user),
+            session_key
         );
+        Ok(())
     }
 
-    /// Verify session key
-    pub fn verify_session_key(env: Env, user: Address, key: BytesN<32>) -> bool {
-        let session_keys: Option<Vec<SessionKey>> = env.storage().persistent()
-            .get(&DataKey::SessionKey(user.clone()));
-        
-        if let Some(keys) = session_keys {
-            let current_time = env.ledger().timestamp();
-            
-            for i in 0..keys.len() {
-                if let Some(sk) = keys.get(i) {
-                    if sk.key == key && current_time < sk.expires_at {
-                        return true;
-                    }
-                }
+    /// List the user's unexpired session keys
+    pub fn list_session_keys(env: Env, user: Address) -> Vec<SessionKey> {
+        let session_keys: Vec<BytesN<32>> = env.storage().persistent()
+            .get(&DataKey::SessionKeys(user.clone()))
+            .unwrap_or(Vec::new(&env));
+
+        let mut active = Vec::new(&env);
+        for public_key in session_keys.iter() {
+            if let Some(key_data) = Self::active_session_key(&env, &user, &public_key) {
+                active.push_back(key_data);
             }
         }
-        
-        false
+
+        active
     }
 
-    /// Setup multi-sig
+    /// Check a session key signature over `payload` without consuming the user's nonce
+    /// Returns false for unknown/expired keys, stale payloads, a wrong nonce or a payload
+    /// bound to another user or contract; traps if the signature does not match the key
+    pub fn verify_session_key(
+        env: Env,
+        user: Address,
+        public_key: BytesN<32>,
+        payload: SessionPayload,
+        signature: BytesN<64>,
+    ) -> bool {
+        Self::check_session_payload(&env, &user, &public_key, &payload, &signature).is_ok()
+    }
+
+    /// Get the user's next nonce, shared by meta-transactions and session payloads
+    pub fn get_nonce(env: Env, user: Address) -> u64 {
+        env.storage().persistent().get(&DataKey::Nonce(user)).unwrap_or(0)
+    }
+
+    /// Setup multi-sig (once; later changes need signer approval)
+    /// signers: signer -> weight; threshold: total weight required;
+    /// class_thresholds: per-class overrides of the threshold (e.g. a higher Admin threshold)
     pub fn setup_multisig(
         env: Env,
         user: Address,
-        signers: Vec<Address>,
+        signers: Map<Signer, u32>,
         threshold: u32,
-    ) {
+        class_thresholds: Map<OperationClass, u32>,
+    ) -> Result<(), AAError> {
         user.require_auth();
-        
-        if threshold == 0 || threshold > signers.len() {
-            panic!("Invalid threshold");
+        Self::require_not_paused(&env)?;
+        Self::require_account(&env, &user)?;
+
+        if env.storage().persistent().has(&DataKey::Signers(user.clone())) {
+            return Err(AAError::MultisigAlreadyConfigured);
         }
-        
+
+        for (class, class_threshold) in class_thresholds.iter() {
+            env.storage().persistent().set(&DataKey::ClassThreshold(user.clone(), class), &class_threshold);
+        }
+        Self::validate_signers(&env, &user, &signers, threshold)?;
+
         env.storage().persistent().set(&DataKey::Signers(user.clone()), &signers);
         env.storage().persistent().set(&DataKey::Threshold(user.clone()), &threshold);
-        env.storage().persistent().set(&DataKey::Mode(user.clone()), &AAMode::MultiSig);
-        
-        env.events().publish(
+        Self::update_feature(&env, &user, Feature::MultiSig, true);
+
+        publish(&env,
             (symbol_short!("multisig"), user),
             (threshold, signers.len())
         );
+        Ok(())
     }
 
-    /// Verify multi-sig
-    pub fn verify_multisig(env: Env, user: Address, signatures: Vec<Address>) -> bool {
-        let signers: Option<Vec<Address>> = env.storage().persistent().get(&DataKey::Signers(user.clone()));
-        let threshold: Option<u32> = env.storage().persistent().get(&DataKey::Threshold(user.clone()));
-        
-        if let (Some(valid_signers), Some(required_threshold)) = (signers, threshold) {
-            let mut valid_count = 0u32;
-            
-            for i in 0..signatures.len() {
-                if let Some(sig_addr) = signatures.get(i) {
-                    // Check if signer is in valid signers list
-                    for j in 0..valid_signers.len() {
-                        if let Some(valid_addr) = valid_signers.get(j) {
-                            if sig_addr == valid_addr {
-                                valid_count += 1;
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
-            
-            valid_count >= required_threshold
-        } else {
-            false
-        }
+    /// Add a weighted signer, approved at the Admin threshold
+    pub fn add_signer(env: Env, user: Address, signer: Signer, weight: u32, approvers: Vec<SignerProof>) -> Result<(), AAError> {
+        Self::change_signers(&env, &user, SignerChange::Add(signer, weight), &approvers)
     }
 
-    /// Execute meta-transaction (gas sponsored)
-    pub fn execute_metatx(
-        env: Env,
-        user: Address,
-        target: Address,
-        function: String,
-        args: Bytes,
-    ) -> Bytes {
-        // Verify AA mode allows meta-tx
-        let mode = Self::get_mode(env.clone(), user.clone());
-        
-        match mode {
-            AAMode::Sponsored => {
-                // Verify sponsor exists
-                let sponsor: Option<Address> = env.storage().persistent().get(&DataKey::Sponsor(user.clone()));
-                if sponsor.is_none() {
-                    panic!("No sponsor set");
-                }
-            },
-            AAMode::SessionKey => {
-                // Session key verification done separately
-            },
-            _ => {
-                panic!("AA mode does not support meta-tx");
-            }
-        }
-        
-        // Deduct from gas pool
-        let mut gas_pool: i128 = env.storage().instance().get(&DataKey::GasPool).unwrap_or(0);
-        let gas_cost = 1000i128; // Simplified gas cost
-        
-        if gas_pool < gas_cost {
-            panic!("Insufficient gas pool");
-        }
-        
-        gas_pool -= gas_cost;
-        env.storage().instance().set(&DataKey::GasPool, &gas_pool);
-        
-        // Emit meta-tx event
-        env.events().publish(
-            (symbol_short!("metatx"), user),
-            (target, function, env.ledger().timestamp())
-        );
-        
-        // Return empty bytes (actual execution would happen here)
-        Bytes::new(&env)
+    /// Remove a signer, approved at the Admin threshold (allowed while paused)
+    pub fn remove_signer(env: Env, user: Address, signer: Signer, approvers: Vec<SignerProof>) -> Result<(), AAError> {
+        Self::change_signers(&env, &user, SignerChange::Remove(signer), &approvers)
     }
 
-    /// Add funds to gas pool (owner only)
-    pub fn fund_gas_pool(env: Env, amount: i128) {
-        let owner: Address = env.storage().instance().get(&DataKey::Owner).unwrap();
-        owner.require_auth();
-        
-        let mut gas_pool: i128 = env.storage().instance().get(&DataKey::GasPool).unwrap_or(0);
-        gas_pool += amount;
-        env.storage().instance().set(&DataKey::GasPool, &gas_pool);
-        
-        env.events().publish(
-            (symbol_short!("fund"), owner),
-            amount
-        );
+    /// Change the Standard threshold, approved at the Admin threshold
+    pub fn change_threshold(env: Env, user: Address, threshold: u32, approvers: Vec<SignerProof>) -> Result<(), AAError> {
+        Self::change_signers(&env, &user, SignerChange::ChangeThreshold(threshold), &approvers)
     }
 
-    /// Get gas pool balance
-    pub fn get_gas_pool(env: Env) -> i128 {
-        env.storage().instance().get(&DataKey::GasPool).unwrap_or(0)
+    /// Replace a signer, approved at the Admin threshold
+    pub fn replace_signer(env: Env, user: Address, old_signer: Signer, new_signer: Signer, approvers: Vec<SignerProof>) -> Result<(), AAError> {
+        Self::change_signers(&env, &user, SignerChange::Replace(old_signer, new_signer), &approvers)
     }
-}
+
+    /// Change a signer's weight, approved at the Admin threshold
+    pub fn set_signer_weight(env: Env, user: Address, signer: Signer, weight: u32, approvers: Vec<SignerProof>) -> Result<(), AAError> {
+        Self::change_signers(&env, &user, SignerChange::SetWeight(signer, weight), &approvers)
+    }
+
+    /// Set the threshold for an operation class, approved at the Admin threshold
+    pub fn set_class_threshold(env: Env, user: Address, class: OperationClass, threshold: u32, approvers: Vec<SignerProof>) -> Result<(), AAError> {
+        Self::change_signers(&env, &user, SignerChange::SetClassThreshold(class, threshold), &approvers)
+    }
+
+    /// Get the user's multisig signer weights and Standard threshold
+    pub fn get_multisig(env: Env, user: Address) -> Option<(Map<Signer, u32>, u32)> {
+        let signers: Map<Signer, u32> = env.storage().persistent().get(&DataKey::Signers(user.clone()))?;
+        let threshold: u32 = env.storage().persistent().get(&DataKey::Threshold(user))?;
+        Some((signers, threshold))
+    }
+
+    /// Get the weight threshold for an operation class (falls back to the Standard threshold)
+    pub fn get_class_threshold(env: Env, user: Address, class: OperationClass) -> Option<u32> {
+        Self::class_threshold(&env, &user, class).ok()
+    }
+
+    /// Verify multi-sig approval of an operation at the Standard threshold
+    /// Every listed signer must authorize (user, op_hash); duplicates and non-signers are rejected.
+    /// Passkey signers approve through the account's `__check_auth` instead
+    pub fn verify_multisig(env: Env, user: Address, signers: Vec<Address>, op_hash: BytesN<32>) -> bool {
+        let approval_args = vec![&env, user.into_val(&env), op_hash.into_val(&env)];
+        let mut proofs = Vec::new(&env);
+        for signer in signers.iter() {
+            proofs.push_back(SignerProof::Address(signer));
+        }
+        Self::class_threshold(&env, &user, OperationClass::Standard)
+            .and_then(|threshold| Self::check_approvals(&env, &user, &proofs, symbol_short!("verify"), approval_args, threshold))
+            .is_ok()
+    }
+
+    /// Propose a call for the user's multisig; the proposer's approval is counted
+    /// The call runs as this contract, so `user` must be the contract's own account
+    /// proposer: address or passkey signer approving `(user, target, function, args, duration, id)`,
+    /// where `id` is the proposal id being created
+    pub fn propose(
+        env: Env,
+        user: Address,
+        proposer: SignerProof,
+        target: Address,
+        function: Symbol,
+        args: Vec<Val>,
+        duration: u64,
+    ) -> Result<u64, AAError> {
+        Self::require_own_account(&env, &user)?;
+        Self::check_target(&env, &target)?;
+        let proposer_signer = Self::proof_signer(&proposer);
+        Self::signer_weight(&env, &user, &proposer_signer)?;
+
+        let id: u64 = env.storage().instance().get(&DataKey::NextProposalId).unwrap_or(0);
+        let approval_args = (user.clone(), target.clone(), function.clone(), args.clone(), duration, id).into_val(&env);
+        Self::require_proof(&env, &proposer, symbol_short!("propose"), approval_args)?;
+        env.storage().instance().set(&DataKey::NextProposalId, &(id + 1));
+
+        let proposal = Proposal {
+            id,
+            user: user.clone(),
+            proposer: proposer_signer.clone(),
+            target,
+            function,
+            args,
+            approvals: vec![&env, proposer_signer.clone()],
+            expires_at: env.ledger().timestamp() + duration,
+            status: ProposalStatus::Pending,
+        };
+        env.storage().persistent().set(&DataKey::Proposal(id), &proposal);
+
+        publish(&env,
+            (symbol_short!("proposed"), user),
+            (id, proposer_signer, proposal.expires_at)
+        );
+
+        Ok(id)
+    }
+
+    /// Approve a pending proposal as one of the user's signers
+    /// signer: address or passkey signer approving `(proposal_id)`
+    pub fn approve(env: Env, proposal_id: u64, signer: SignerProof) -> Result<(), AAError> {
+        let mut proposal = Self::pending_proposal(&env, proposal_id)?;
+        let approver = Self::proof_signer(&signer);
+        Self::signer_weight(&env, &proposal.user, &approver)?;
+        if proposal.approvals.contains(&approver) {
+            return Err(AAError::DuplicateSigner);
+        }
+        Self::require_proof(&env, &signer, symbol_short!("approve"), vec![&env, proposal_id.into_val(&env)])?;
+
+        proposal.approvals.push_back(approver.clone());
+        env.storage().persistent().set(&DataKey::Proposal(proposal_id), &proposal);
+
+        publish(&env,
+            (symbol_short!("approved"), proposal.user),
+            (proposal_id, approver, proposal.approvals.len())
+        );
+        Ok(())
+    }
+
+    /// Cancel a pending proposal (proposer only)
+    /// proposer: the proposer's address or passkey approving `(proposal_id)`
+    pub fn cancel_proposal(env: Env, proposal_id: u64, proposer: SignerProof) -> Result<(), AAError> {
+        let mut proposal: Proposal = env.storage().persistent()
+            .get(&DataKey::Proposal(proposal_id))
+            .ok_or(AAError::ProposalNotFound)?;
+        if Self::proof_signer(&proposer) != proposal.proposer {
+            return Err(AAError::NotAuthorized);
+        }
+        Self::require_proof(&env, &proposer, symbol_short!("cancel"), vec![&env, proposal_id.into_val(&env)])?;
+
+        if proposal.status != ProposalStatus::Pending {
+            return Err(AAError::ProposalNotPending);
+        }
+
+        proposal.status = ProposalStatus::Cancelled;
+        env.storage().persistent().set(&DataKey::Proposal(proposal_id), &proposal);
+
+        publish(&env,
+            (symbol_short!("cancelled"), proposal.user),
+            proposal_id
+        );
+        Ok(())
+    }
+
+    /// Execute a proposal whose current signers' approvals meet the threshold for its class
+    pub fn execute_proposal(env: Env, proposal_id: u64) -> Result<Val, AAError> {
+        Self::require_not_paused(&env)?;
+        let mut proposal = Self::pending_proposal(&env, proposal_id)?;
+        Self::require_own_account(&env, &proposal.user)?;
+
+        let class = Self::call_class(&env, &proposal.user, &proposal.target, &proposal.function);
+        let threshold = Self::class_threshold(&env, &proposal.user, class)?;
+
+        // Approvals from signers removed since approving no longer count
+        let signers: Map<Signer, u32> = env.storage().persistent()
+            .get(&DataKey::Signers(proposal.user.clone()))
+            .ok_or(AAError::MultisigNotConfigured)?;
+        let weight: u64 = proposal.approvals.iter()
+            .filter_map(|approver| signers.get(approver))
+            .map(|w| w as u64)
+            .sum();
+        if weight < threshold as u64 {
+            return Err(AAError::ThresholdNotMet);
+        }
+
+        // Mark executed before the call so it cannot be replayed by re-entry
+        proposal.status = ProposalStatus::Executed;
+        env.storage().persistent().set(&DataKey::Proposal(proposal_id), &proposal);
+
+        let result = Self::dispatch(&env, &proposal.target, &proposal.function, proposal.args.clone())?;
+
+        publish(&env,
+            (symbol_short!("executed"), proposal.user),
+            proposal_id
+        );
+
+        Ok(result)
+    }
+
+    /// Get a multisig proposal
+    pub fn get_proposal(env: Env, proposal_id: u64) -> Option<Proposal> {
+        env.storage().persistent().get(&DataKey::Proposal(proposal_id))
+    }
+
+    /// Execute meta-transaction (gas sponsored)
+    /// relayer: submitter reimbursed in the gas token from the sponsor's pool
+    /// max_fee: most the meta-tx may cost the user, in their fee token with the paymaster,
+    /// otherwise in gas token units charged to their sponsor; signed with the call
+    /// nonce: must equal `get_nonce(user)`; deadline: last valid ledger timestamp
+    /// session: session key signature instead of the user's own authorization,
+    /// checked against the key's scope
+    /// Calls run as this contract, so `user` must be the contract's own account and the
+    /// target cannot be the contract or the gas token
+    pub fn execute_metatx(
+        env: Env,
+        user: Address,
+        relayer: Address,
+        network_fee: i128,
+        max_fee: i128,
+        target: Address,
+        function: Symbol,
+        args: Vec<Val>,
+        nonce: u64,
+        deadline: u64,
+        session: Option<SessionAuth>,
+    ) -> Result<Val, AAError> {
+        let sponsor = Self::begin_metatx(&env, &user, &relayer, deadline, session.is_some())?;
+
+        // Authorize the exact call, bound to the nonce and deadline
+        match session {
+            Some(session) => {
+                let payload = SessionPayload {
+                    user: user.clone(),
+                    verifier: env.current_contract_address(),
+                    contract: target.clone(),
+                    function: function.clone(),
+                    args_hash: env.crypto().sha256(&args.clone().to_xdr(&env)).to_bytes(),
+                    max_fee,
+                    nonce,
+                    expires_at: deadline,
+                };
+
+                let key = Self::check_session_payload(&env, &user, &session.public_key, &payload, &session.signature)?;
+                Self::check_scope(&key, &target, &function, &args)?;
+                Self::consume_nonce(&env, &user, nonce)?;
+            },
+            None => {
+                user.require_auth_for_args(
+                    (target.clone(), function.clone(), args.clone(), max_fee, nonce, deadline).into_val(&env)
+                );
+                Self::consume_nonce(&env, &user, nonce)?;
+            },
+        }
+
+        let calls = vec![&env, Call { contract: target.clone(), function: function.clone(), args: args.clone() }];
+        let receipt = Self::compute_fee(&env, &sponsor, &relayer, network_fee, &calls)?;
+        Self::charge_sponsor(&env, &user, &receipt, max_fee, &vec![&env, target.clone()])?;
+
+        let result = Self::dispatch(&env, &target, &function, args)?;
+
+        // Emit meta-tx event
+        publish(&env,
+            (symbol_short!("metatx"), user.clone()),
+            (target, function, sponsor, receipt.total, env.ledger().timestamp())
+        );
+        Self::emit_receipt(&env, &user, receipt);
+
+        Ok(result)
+    }
+
+    /// Execute several calls atomically as one sponsored meta-transaction
+    /// One nonce and one authorization (over `(calls, max_fee, nonce, deadline)`, or a session key's
+    /// signed `BatchPayload`) cover the batch; gas is charged once and a failing call reverts all
+    pub fn execute_batch(
+        env: Env,
+        user: Address,
+        relayer: Address,
+        network_fee: i128,
+        max_fee: i128,
+        calls: Vec<Call>,
+        nonce: u64,
+        deadline: u64,
+        session: Option<SessionAuth>,
+    ) -> Result<Vec<Val>, AAError> {
+        if calls.is_empty() {
+            return Err(AAError::EmptyBatch);
+        }
+
+        let sponsor = Self::begin_metatx(&env, &user, &relayer, deadline, session.is_some())?;
+
+        match session {
+            Some(session) => {
+                let payload = BatchPayload {
+                    user: user.clone(),
+                    verifier: env.current_contract_address(),
+                    calls_hash: env.crypto().sha256(&calls.clone().to_xdr(&env)).to_bytes(),
+                    max_fee,
+                    nonce,
+                    expires_at: deadline,
+                };
+
+                // Every call must fall within the session key's scopes
+                let key = Self::check_session_message(&env, &user, &session.public_key, &payload.to_xdr(&env), nonce, deadline, &session.signature)?;
+                for call in calls.iter() {
+                    Self::check_scope(&key, &call.contract, &call.function, &call.args)?;
+                }
+                Self::consume_nonce(&env, &user, nonce)?;
+            },
+            None => {
+                user.require_auth_for_args((calls.clone(), max_fee, nonce, deadline).into_val(&env));
+                Self::consume_nonce(&env, &user, nonce)?;
+            },
+        }
+
+        let mut targets = Vec::new(&env);
+        for call in calls.iter() {
+            targets.push_back(call.contract);
+        }
+        let receipt = Self::compute_fee(&env, &sponsor, &relayer, network_fee, &calls)?;
+        Self::charge_sponsor(&env, &user, &receipt, max_fee, &targets)?;
+
+        let mut results = Vec::new(&env);
+        for call in calls.iter() {
+            results.push_back(Self::dispatch(&env, &call.contract, &call.function, call.args)?);
+        }
+
+        publish(&env,
+            (symbol_short!("batch"), user.clone()),
+            (calls.len(), sponsor, receipt.total, env.ledger().timestamp())
+        );
+        Self::emit_receipt(&env, &user, receipt);
+
+        Ok(results)
+    }
+
+    /// Update the meta-tx fee schedule (owner, or a wallet's fee admin)
+    pub fn set_fee_schedule(env: Env, schedule: FeeSchedule) -> Result<(), AAError> {
+        Self::fee_admin(&env)?.require_auth();
+
+        if schedule.base_fee < 0 || schedule.call_fee < 0 || schedule.per_byte_fee < 0 || schedule.max_network_fee < 0 {
+            return Err(AAError::InvalidFeeSchedule);
+        }
+
+        env.storage().instance().set(&DataKey::FeeSchedule, &schedule);
+
+        publish(&env,
+            (symbol_short!("fees"),),
+            (schedule.base_fee, schedule.call_fee, schedule.per_byte_fee, schedule.max_network_fee)
+        );
+        Ok(())
+    }
+
+    /// Get the meta-tx fee schedule (defaults to a flat 1000 per meta-tx, no network fee)
+    pub fn get_fee_schedule(env: Env) -> FeeSchedule {
+        env.storage().instance()
+            .get(&DataKey::FeeSchedule)
+            .unwrap_or(FeeSchedule {
+                base_fee: 1000,
+                call_fee: 0,
+                per_byte_fee: 0,
+                target_multipliers: Map::new(&env),
+                max_network_fee: 0,
+            })
+    }
+
+    /// Set the paymaster exchange rate for a fee token (owner, or a wallet's fee admin)
+    /// rate: fee token units per gas token unit, scaled by 10^7
+    pub fn set_fee_token_rate(env: Env, token: Address, rate: i128) -> Result<(), AAError> {
+        Self::fee_admin(&env)?.require_auth();
+
+        if rate <= 0 {
+            return Err(AAError::InvalidAmount);
+        }
+        // Repaying a sponsor in the gas token would pay it out of the pooled deposits
+        if token == Self::get_gas_token(env.clone())? {
+            return Err(AAError::GasTokenNotAllowed);
+        }
+
+        env.storage().persistent().set(&DataKey::FeeTokenRate(token.clone()), &rate);
+
+        publish(&env,
+            (symbol_short!("fee_rate"), token),
+            rate
+        );
+        Ok(())
+    }
+
+    /// Stop accepting a fee token (owner, or a wallet's fee admin)
+    pub fn remove_fee_token(env: Env, token: Address) -> Result<(), AAError> {
+        Self::fee_admin(&env)?.require_auth();
+
+        env.storage().persistent().remove(&DataKey::FeeTokenRate(token.clone()));
+
+        publish(&env,
+            (symbol_short!("fee_rate"), token),
+            0i128
+        );
+        Ok(())
+    }
+
+    /// Get the paymaster exchange rate for a fee token
+    pub fn get_fee_token_rate(env: Env, token: Address) -> Option<i128> {
+        env.storage().persistent().get(&DataKey::FeeTokenRate(token))
+    }
+
+    /// Convert a gas token amount to a fee token amount at the current rate (rounded up)
+    pub fn quote_fee(env: Env, token: Address, gas_amount: i128) -> Result<i128, AAError> {
+        let rate = Self::get_fee_token_rate(env, token).ok_or(AAError::UnsupportedFeeToken)?;
+        Ok((gas_amount * rate + RATE_SCALE - 1) / RATE_SCALE)
+    }
+
+    /// Pay meta-tx fees to the sponsor in `token` from the account's own balance
+    /// The per-tx bound is the `max_fee` signed with each meta-tx
+    pub fn set_fee_payment(env: Env, user: Address, token: Address) -> Result<(), AAError> {
+        user.require_auth();
+        Self::require_not_paused(&env)?;
+        Self::require_account(&env, &user)?;
+
+        if Self::get_fee_token_rate(env.clone(), token.clone()).is_none() {
+            return Err(AAError::UnsupportedFeeToken);
+        }
+
+        env.storage().persistent().set(&DataKey::FeePayment(user.clone()), &FeePayment { token });
+        Self::update_feature(&env, &user, Feature::Paymaster, true);
+        Ok(())
+    }
+
+    /// Get the user's paymaster settings
+    pub fn get_fee_payment(env: Env, user: Address) -> Option<FeePayment> {
+        env.storage().persistent().get(&DataKey::FeePayment(user))
+    }
+
+    /// Deposit gas tokens into the sponsor's own gas pool
+    pub fn fund_gas_pool(env: Env, sponsor: Address, amount: i128) -> Result<(), AAError> {
+        sponsor.require_auth();
+
+        if amount <= 0 {
+            return Err(AAError::InvalidAmount);
+        }
+
+        Self::gas_token(&env)?.transfer(&sponsor, env.current_contract_address(), &amount);
+
+        let mut pool = Self::get_sponsor_pool(env.clone(), sponsor.clone());
+        pool.balance += amount;
+        env.storage().persistent().set(&DataKey::SponsorPool(sponsor.clone()), &pool);
+        Self::adjust_gas_pool(&env, amount);
+
+        publish(&env,
+            (symbol_short!("fund"), sponsor),
+            amount
+        );
+        Ok(())
+    }
+
+    /// Withdraw unused gas tokens from the sponsor's gas pool
+    pub fn withdraw_gas_pool(env: Env, sponsor: Address, amount: i128) -> Result<(), AAError> {
+        sponsor.require_auth();
+
+        let mut pool = Self::get_sponsor_pool(env.clone(), sponsor.clone());
+        if amount <= 0 || amount > pool.balance {
+            return Err(AAError::InvalidAmount);
+        }
+
+        pool.balance -= amount;
+        env.storage().persistent().set(&DataKey::SponsorPool(sponsor.clone()), &pool);
+        Self::adjust_gas_pool(&env, -amount);
+
+        Self::gas_token(&env)?.transfer(&env.current_contract_address(), &sponsor, &amount);
+
+        publish(&env,
+            (symbol_short!("withdraw"), sponsor),
+            amount
+        );
+        Ok(())
+    }
+
+    /// Get a sponsor's gas pool balance and spend
+    pub fn get_sponsor_pool(env: Env, sponsor: Address) -> SponsorPool {
+        env.storage().persistent()
+            .get(&DataKey::SponsorPool(sponsor))
+            .unwrap_or(SponsorPool { balance: 0, spent: 0 })
+    }
+
+    /// Get total gas pool balance across all sponsors
+    pub fn get_gas_pool(env: Env) -> i128 {
+        env.storage().instance().get(&DataKey::GasPool).unwrap_or(0)
+    }
+
+    /// Get the gas token contract address
+    pub fn get_gas_token(env: Env) -> Result<Address, AAError> {
+        env.storage().instance().get(&DataKey::GasToken).ok_or(AAError::NotInitialized)
+    }
+
+    /// Compare pool accounting with the contract's gas token balance
+    pub fn get_pool_reconciliation(env: Env) -> Result<PoolReconciliation, AAError> {
+        let accounted = Self::get_gas_pool(env.clone());
+        let balance = Self::gas_token(&env)?.balance(&env.current_contract_address());
+
+        Ok(PoolReconciliation {
+            accounted,
+            balance,
+            surplus: balance - accounted,
+        })
+    }
+}
+
+#[contractimpl]
+impl CustomAccountInterface for AccountAbstraction {
+    type Signature = AccountSignature;
+    type Error = AAError;
+
+    /// Authorize calls made by this contract's own address, routed by its AA features
+    fn __check_auth(
+        env: Env,
+        signature_payload: Hash<32>,
+        signature: AccountSignature,
+        auth_contexts: Vec<Context>,
+    ) -> Result<(), AAError> {
+        let account = env.current_contract_address();
+        let features = Self::get_features(env.clone(), account.clone());
+
+        // Delegated keys stop with the pause; the owner and multisig signers keep control
+        if matches!(signature, AccountSignature::SessionKey(_) | AccountSignature::Passkey(_)) {
+            Self::require_not_paused(&env)?;
+        }
+
+        // Sponsor deposits are held in this contract's gas token balance, so no signature
+        // may authorize a gas token call as the account
+        let gas_token = Self::get_gas_token(env.clone())?;
+        for context in auth_contexts.iter() {
+            if let Context::Contract(call) = context {
+                if call.contract == gas_token {
+                    return Err(AAError::ReservedTarget);
+                }
+            }
+        }
+
+        // Session keys stay usable for their scopes when multisig is also active;
+        // the owner key cannot bypass multisig
+        match signature {
+            AccountSignature::MultiSig(signers) if features.multisig => {
+                Self::check_multisig(&env, &account, &signature_payload, &signers, &auth_contexts)
+            },
+            AccountSignature::SessionKey(session) if features.session_keys => {
+                Self::check_session_signature(&env, &account, &signature_payload, &session, &auth_contexts)
+            },
+            AccountSignature::Passkey(passkey) if features.session_keys => {
+                Self::check_passkey_session(&env, &account, &signature_payload, &passkey, &auth_contexts)
+            },
+            AccountSignature::Owner if !features.multisig => {
+                let owner = Self::owner(&env)?;
+                owner.require_auth_for_args(vec![&env, signature_payload.to_bytes().into_val(&env)]);
+                Ok(())
+            },
+            _ => Err(AAError::SignatureNotAllowed),
+        }
+    }
+}
+
+impl AccountAbstraction {
+    fn owner(env: &Env) -> Result<Address, AAError> {
+        env.storage().instance().get(&DataKey::Owner).ok_or(AAError::NotInitialized)
+    }
+
+    fn fee_admin(env: &Env) -> Result<Address, AAError> {
+        match env.storage().instance().get(&DataKey::FeeAdmin) {
+            Some(fee_admin) => Ok(fee_admin),
+            None => Self::owner(env),
+        }
+    }
+
+    fn require_not_paused(env: &Env) -> Result<(), AAError> {
+        if Self::is_paused(env.clone()) {
+            return Err(AAError::Paused);
+        }
+        Ok(())
+    }
+
+    /// Turn one feature on or off without touching the others
+    fn update_feature(env: &Env, user: &Address, feature: Feature, enabled: bool) {
+        let mut features = Self::get_features(env.clone(), user.clone());
+        match feature {
+            Feature::Sponsored => features.sponsored = enabled,
+            Feature::SessionKeys => features.session_keys = enabled,
+            Feature::MultiSig => features.multisig = enabled,
+            Feature::Paymaster => features.paymaster = enabled,
+        }
+        env.storage().persistent().set(&DataKey::Features(user.clone()), &features);
+
+        publish(env,
+            (symbol_short!("feature"), user.clone()),
+            (feature, enabled)
+        );
+    }
+
+    /// Store a session key (re-adding a key replaces its expiry and scopes)
+    fn register_session_key(
+        env: &Env,
+        user: &Address,
+        session_key: BytesN<32>,
+        passkey: Option<BytesN<65>>,
+        duration: u64,
+        permissions: Vec<SessionScope>,
+    ) -> Result<(), AAError> {
+        let expires_at = env.ledger().timestamp() + duration;
+
+        let mut session_keys = Self::prune_session_keys(env, user);
+        if !session_keys.contains(&session_key) {
+            if session_keys.len() >= MAX_SESSION_KEYS {
+                return Err(AAError::TooManySessionKeys);
+            }
+            session_keys.push_back(session_key.clone());
+        }
+
+        let key_data = SessionKey {
+            key: session_key.clone(),
+            expires_at,
+            permissions,
+            passkey,
+        };
+
+        env.storage().persistent().set(&DataKey::SessionKey(user.clone(), session_key), &key_data);
+        env.storage().persistent().set(&DataKey::SessionKeys(user.clone()), &session_keys);
+
+        Self::update_feature(env, user, Feature::SessionKeys, true);
+
+        publish(env,
+            (symbol_short!("session"), user.clone()),
+            expires_at
+        );
+        Ok(())
+    }
+
+    /// Find an unexpired ed25519 session key registered for the user
+    fn ed25519_session_key(env: &Env, user: &Address, public_key: &BytesN<32>) -> Result<SessionKey, AAError> {
+        Self::active_session_key(env, user, public_key)
+            .filter(|sk| sk.passkey.is_none())
+            .ok_or(AAError::InvalidSessionKey)
+    }
+
+    /// Find an unexpired session key registered for the user
+    fn active_session_key(env: &Env, user: &Address, public_key: &BytesN<32>) -> Option<SessionKey> {
+        let key_data: Option<SessionKey> = env.storage().persistent()
+            .get(&DataKey::SessionKey(user.clone(), public_key.clone()));
+
+        key_data.filter(|sk| env.ledger().timestamp() < sk.expires_at)
+    }
+
+    /// Delete the user's expired session keys and return the remaining public keys
+    fn prune_session_keys(env: &Env, user: &Address) -> Vec<BytesN<32>> {
+        let session_keys: Vec<BytesN<32>> = env.storage().persistent()
+            .get(&DataKey::SessionKeys(user.clone()))
+            .unwrap_or(Vec::new(env));
+
+        let mut remaining = Vec::new(env);
+        for public_key in session_keys.iter() {
+            if Self::active_session_key(env, user, &public_key).is_some() {
+                remaining.push_back(public_key);
+            } else {
+                env.storage().persistent().remove(&DataKey::SessionKey(user.clone(), public_key));
+            }
+        }
+
+        remaining
+    }
+
+    /// Check a call against the session key's scopes, including their i128 argument limits
+    fn check_scope(
+        session_key: &SessionKey,
+        contract: &Address,
+        function: &Symbol,
+        args: &Vec<Val>,
+    ) -> Result<(), AAError> {
+        let allowed = session_key.permissions.iter().any(|scope| {
+            scope.contract == *contract
+                && scope.function == *function
+                && scope.arg_limits.iter().all(|limit| {
+                    args.get(limit.index)
+                        .and_then(|arg| i128::try_from_val(args.env(), &arg).ok())
+                        .is_some_and(|amount| amount <= limit.max)
+                })
+        });
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(AAError::OutOfScope)
+        }
+    }
+
+    /// Verify a signed session payload; the caller consumes the nonce
+    fn check_session_payload(
+        env: &Env,
+        user: &Address,
+        public_key: &BytesN<32>,
+        payload: &SessionPayload,
+        signature: &BytesN<64>,
+    ) -> Result<SessionKey, AAError> {
+        if payload.user != *user || payload.verifier != env.current_contract_address() {
+            return Err(AAError::PayloadMismatch);
+        }
+
+        let message = payload.clone().to_xdr(env);
+        Self::check_session_message(env, user, public_key, &message, payload.nonce, payload.expires_at, signature)
+    }
+
+    /// Verify a session key signature over an XDR-encoded payload signed for the user's current nonce
+    fn check_session_message(
+        env: &Env,
+        user: &Address,
+        public_key: &BytesN<32>,
+        message: &Bytes,
+        nonce: u64,
+        expires_at: u64,
+        signature: &BytesN<64>,
+    ) -> Result<SessionKey, AAError> {
+        let session_key = Self::ed25519_session_key(env, user, public_key)?;
+
+        if env.ledger().timestamp() > expires_at {
+            return Err(AAError::SignatureExpired);
+        }
+
+        if nonce != Self::get_nonce(env.clone(), user.clone()) {
+            return Err(AAError::InvalidNonce);
+        }
+
+        env.crypto().ed25519_verify(public_key, message, signature);
+
+        Ok(session_key)
+    }
+
+    /// Common meta-tx checks; returns the sponsor paying for it
+    fn begin_metatx(env: &Env, user: &Address, relayer: &Address, deadline: u64, uses_session: bool) -> Result<Address, AAError> {
+        relayer.require_auth();
+        Self::require_not_paused(env)?;
+        Self::require_own_account(env, user)?;
+        
+        // Meta-tx requires sponsorship; session authorization requires session keys
+        let features = Self::get_features(env.clone(), user.clone());
+        
+        if !features.sponsored {
+            return Err(AAError::SponsorshipNotEnabled);
+        }
+        if uses_session && !features.session_keys {
+            return Err(AAError::SignatureNotAllowed);
+        }
+        
+        // Meta-tx costs are charged to the user's sponsor
+        let sponsor = Self::sponsor_of(env, user)?;
+        
+        if env.ledger().timestamp() > deadline {
+            return Err(AAError::SignatureExpired);
+        }
+        
+        Ok(sponsor)
+    }
+
+    /// Price a meta-tx under the fee schedule
+    fn compute_fee(env: &Env, sponsor: &Address, relayer: &Address, network_fee: i128, calls: &Vec<Call>) -> Result<FeeReceipt, AAError> {
+        let schedule = Self::get_fee_schedule(env.clone());
+        
+        if network_fee < 0 {
+            return Err(AAError::InvalidAmount);
+        }
+        if network_fee > schedule.max_network_fee {
+            return Err(AAError::FeeAboveCap);
+        }
+        
+        let mut execution_fee = 0i128;
+        for call in calls.iter() {
+            let arg_bytes = call.args.to_xdr(env).len() as i128;
+            let multiplier = schedule.target_multipliers.get(call.contract).unwrap_or(FEE_MULTIPLIER_BASE);
+            let call_cost = schedule.call_fee + schedule.per_byte_fee * arg_bytes;
+            execution_fee += call_cost * multiplier as i128 / FEE_MULTIPLIER_BASE as i128;
+        }
+        
+        Ok(FeeReceipt {
+            sponsor: sponsor.clone(),
+            relayer: relayer.clone(),
+            network_fee,
+            base_fee: schedule.base_fee,
+            execution_fee,
+            total: network_fee + schedule.base_fee + execution_fee,
+        })
+    }
+
+    /// Charge a meta-tx to the sponsor's pool under its policy and reimburse the relayer
+    fn charge_sponsor(env: &Env, user: &Address, receipt: &FeeReceipt, max_fee: i128, targets: &Vec<Address>) -> Result<(), AAError> {
+        let paymaster = Self::get_features(env.clone(), user.clone()).paymaster;
+        if !paymaster && receipt.total > max_fee {
+            return Err(AAError::SlippageExceeded);
+        }
+
+        let mut pool = Self::get_sponsor_pool(env.clone(), receipt.sponsor.clone());
+        if pool.balance < receipt.total {
+            return Err(AAError::InsufficientGasPool);
+        }
+        
+        Self::apply_sponsor_policy(env, user, targets, receipt.total)?;
+        
+        pool.balance -= receipt.total;
+        pool.spent += receipt.total;
+        env.storage().persistent().set(&DataKey::SponsorPool(receipt.sponsor.clone()), &pool);
+        Self::adjust_gas_pool(env, -receipt.total);
+        
+        Self::gas_token(env)?.transfer(&env.current_contract_address(), &receipt.relayer, &receipt.total);
+        
+        if paymaster {
+            Self::collect_fee_payment(env, user, receipt, max_fee)?;
+        }
+        Ok(())
+    }
+
+    /// Paymaster: the account repays the sponsor in its fee token, within the signed max_fee
+    fn collect_fee_payment(env: &Env, user: &Address, receipt: &FeeReceipt, max_fee: i128) -> Result<(), AAError> {
+        let payment = Self::get_fee_payment(env.clone(), user.clone())
+            .ok_or(AAError::PaymasterNotConfigured)?;
+        let rate = Self::get_fee_token_rate(env.clone(), payment.token.clone())
+            .ok_or(AAError::UnsupportedFeeToken)?;
+        
+        let amount = (receipt.total * rate + RATE_SCALE - 1) / RATE_SCALE;
+        if amount > max_fee {
+            return Err(AAError::SlippageExceeded);
+        }
+        
+        token::Client::new(env, &payment.token).transfer(user, &receipt.sponsor, &amount);
+        
+        publish(env,
+            (symbol_short!("paymaster"), user.clone()),
+            (payment.token, amount, receipt.sponsor.clone(), rate)
+        );
+        Ok(())
+    }
+
+    fn emit_receipt(env: &Env, user: &Address, receipt: FeeReceipt) {
+        publish(env,
+            (symbol_short!("receipt"), user.clone()),
+            receipt
+        );
+    }
+
+    /// Invoke a meta-tx call; a failure aborts the whole meta-tx, rolling back nonce and gas accounting
+    fn dispatch(env: &Env, target: &Address, function: &Symbol, args: Vec<Val>) -> Result<Val, AAError> {
+        Self::check_target(env, target)?;
+
+        match env.try_invoke_contract::<Val, InvokeError>(target, function, args) {
+            Ok(Ok(value)) => Ok(value),
+            _ => Err(AAError::CallFailed),
+        }
+    }
+
+    /// Dispatched calls run with the contract's own authority, so only its own account may make them
+    fn require_own_account(env: &Env, user: &Address) -> Result<(), AAError> {
+        if *user != env.current_contract_address() {
+            return Err(AAError::NotOwnAccount);
+        }
+        Ok(())
+    }
+
+    /// A per-user wallet only holds account state for its own address
+    fn require_account(env: &Env, user: &Address) -> Result<(), AAError> {
+        if Self::is_wallet(env.clone()) {
+            return Self::require_own_account(env, user);
+        }
+        Ok(())
+    }
+
+    /// The contract's gas token balance backs every sponsor pool, so neither it nor the
+    /// contract itself can be the target of a dispatched call
+    fn check_target(env: &Env, target: &Address) -> Result<(), AAError> {
+        let reserved = *target == env.current_contract_address()
+            || Self::get_gas_token(env.clone()).is_ok_and(|gas_token| gas_token == *target);
+        if reserved {
+            return Err(AAError::ReservedTarget);
+        }
+        Ok(())
+    }
+
+    fn sponsor_of(env: &Env, user: &Address) -> Result<Address, AAError> {
+        env.storage().persistent()
+            .get(&DataKey::Sponsor(user.clone()))
+            .ok_or(AAError::NoSponsor)
+    }
+
+    /// Usage for the current quota period
+    fn current_usage(env: &Env, user: &Address, policy: Option<&SponsorPolicy>) -> SponsorUsage {
+        let current_time = env.ledger().timestamp();
+        let mut usage: SponsorUsage = env.storage().persistent()
+            .get(&DataKey::SponsorUsage(user.clone()))
+            .unwrap_or(SponsorUsage { spent: 0, period_start: current_time, period_tx_count: 0 });
+
+        if let Some(policy) = policy {
+            if policy.period > 0 && current_time >= usage.period_start + policy.period {
+                usage.period_start = current_time;
+                usage.period_tx_count = 0;
+            }
+        }
+
+        usage
+    }
+
+    /// Check a meta-tx against the sponsor's policy and record its usage
+    fn apply_sponsor_policy(env: &Env, user: &Address, targets: &Vec<Address>, cost: i128) -> Result<(), AAError> {
+        let policy: Option<SponsorPolicy> = env.storage().persistent().get(&DataKey::SponsorPolicy(user.clone()));
+        let mut usage = Self::current_usage(env, user, policy.as_ref());
+
+        if let Some(policy) = &policy {
+            if policy.expires_at.is_some_and(|expires_at| env.ledger().timestamp() > expires_at) {
+                return Err(AAError::SponsorshipExpired);
+            }
+            if !policy.allowed_targets.is_empty() && !targets.iter().all(|t| policy.allowed_targets.contains(&t)) {
+                return Err(AAError::TargetNotAllowed);
+            }
+            if policy.max_spend.is_some_and(|max| usage.spent + cost > max) {
+                return Err(AAError::SpendLimitExceeded);
+            }
+            if policy.tx_quota.is_some_and(|quota| usage.period_tx_count >= quota) {
+                return Err(AAError::TxQuotaExceeded);
+            }
+        }
+
+        usage.spent += cost;
+        usage.period_tx_count += 1;
+        env.storage().persistent().set(&DataKey::SponsorUsage(user.clone()), &usage);
+
+        Ok(())
+    }
+
+    fn gas_token(env: &Env) -> Result<token::Client<'_>, AAError> {
+        Ok(token::Client::new(env, &Self::get_gas_token(env.clone())?))
+    }
+
+    /// Apply a change to the total of all sponsor balances
+    fn adjust_gas_pool(env: &Env, delta: i128) {
+        let gas_pool: i128 = env.storage().instance().get(&DataKey::GasPool).unwrap_or(0);
+        env.storage().instance().set(&DataKey::GasPool, &(gas_pool + delta));
+    }
+
+    /// Check the user's next nonce and advance it
+    fn consume_nonce(env: &Env, user: &Address, nonce: u64) -> Result<(), AAError> {
+        let expected = Self::get_nonce(env.clone(), user.clone());
+        if nonce != expected {
+            return Err(AAError::InvalidNonce);
+        }
+
+        env.storage().persistent().set(&DataKey::Nonce(user.clone()), &(expected + 1));
+        Ok(())
+    }
+
+    /// Verify an ed25519 signature from an unexpired session key of the account
+    fn check_session_signature(
+        env: &Env,
+        account: &Address,
+        signature_payload: &Hash<32>,
+        session: &SessionSignature,
+        auth_contexts: &Vec<Context>,
+    ) -> Result<(), AAError> {
+        let session_key = Self::ed25519_session_key(env, account, &session.public_key)?;
+        Self::check_session_contexts(&session_key, auth_contexts)?;
+
+        env.crypto().ed25519_verify(
+            &session.public_key,
+            &signature_payload.to_bytes().into(),
+            &session.signature,
+        );
+
+        Ok(())
+    }
+
+    /// Verify a WebAuthn assertion from an unexpired passkey session key of the account
+    fn check_passkey_session(
+        env: &Env,
+        account: &Address,
+        signature_payload: &Hash<32>,
+        passkey: &WebAuthnSignature,
+        auth_contexts: &Vec<Context>,
+    ) -> Result<(), AAError> {
+        let key_id: BytesN<32> = env.crypto().sha256(&passkey.public_key.clone().into()).into();
+        let session_key = Self::active_session_key(env, account, &key_id)
+            .filter(|sk| sk.passkey.as_ref() == Some(&passkey.public_key))
+            .ok_or(AAError::InvalidSessionKey)?;
+        Self::check_session_contexts(&session_key, auth_contexts)?;
+
+        Self::verify_webauthn(env, signature_payload, passkey)
+    }
+
+    /// Check every authorized call against a session key's scopes
+    fn check_session_contexts(session_key: &SessionKey, auth_contexts: &Vec<Context>) -> Result<(), AAError> {
+        for context in auth_contexts.iter() {
+            match context {
+                Context::Contract(call) => {
+                    Self::check_scope(session_key, &call.contract, &call.fn_name, &call.args)?
+                },
+                // Session keys never authorize contract deployments
+                _ => return Err(AAError::OutOfScope),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verify a WebAuthn assertion over `challenge`; traps if the signature does not match the key
+    fn verify_webauthn(env: &Env, challenge: &Hash<32>, assertion: &WebAuthnSignature) -> Result<(), AAError> {
+        // authenticatorData = rpIdHash (32) || flags (1) || signCount (4) || extensions
+        let flags = assertion.authenticator_data.get(32).ok_or(AAError::InvalidWebAuthn)?;
+        if assertion.authenticator_data.len() < 37 || flags & 0x01 == 0 {
+            return Err(AAError::InvalidWebAuthn);   // User presence required
+        }
+
+        let len = assertion.client_data_json.len() as usize;
+        if len > MAX_CLIENT_DATA_LEN {
+            return Err(AAError::InvalidWebAuthn);
+        }
+        let mut buffer = [0u8; MAX_CLIENT_DATA_LEN];
+        let client_data = &mut buffer[..len];
+        assertion.client_data_json.copy_into_slice(client_data);
+
+        // "challenge":"<43 base64url chars>"
+        let mut expected_challenge = [0u8; 57];
+        expected_challenge[..13].copy_from_slice(b"\"challenge\":\"");
+        Self::base64url_encode(&challenge.to_array(), &mut expected_challenge[13..56]);
+        expected_challenge[56] = b'"';
+
+        let contains = |needle: &[u8]| client_data.windows(needle.len()).any(|window| window == needle);
+        if !contains(b"\"type\":\"webauthn.get\"") || !contains(&expected_challenge) {
+            return Err(AAError::InvalidWebAuthn);
+        }
+
+        let mut message = assertion.authenticator_data.clone();
+        message.extend_from_array(&env.crypto().sha256(&assertion.client_data_json).to_array());
+        let digest = env.crypto().sha256(&message);
+
+        env.crypto().secp256r1_verify(&assertion.public_key, &digest, &assertion.signature);
+
+        Ok(())
+    }
+
+    /// Unpadded base64url encoding of 32 bytes into 43 characters
+    fn base64url_encode(input: &[u8; 32], output: &mut [u8]) {
+        const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+        let mut bits = 0u32;
+        let mut bit_count = 0;
+        let mut i = 0;
+        for byte in input {
+            bits = (bits << 8) | *byte as u32;
+            bit_count += 8;
+            while bit_count >= 6 {
+                bit_count -= 6;
+                output[i] = ALPHABET[((bits >> bit_count) & 0x3f) as usize];
+                i += 1;
+            }
+        }
+        if bit_count > 0 {
+            output[i] = ALPHABET[((bits << (6 - bit_count)) & 0x3f) as usize];
+        }
+    }
+
+    /// Require each distinct signer to approve the payload and check the threshold
+    fn check_multisig(
+        env: &Env,
+        account: &Address,
+        signature_payload: &Hash<32>,
+        proofs: &Vec<SignerProof>,
+        auth_contexts: &Vec<Context>,
+    ) -> Result<(), AAError> {
+        // The strictest class among the authorized calls applies
+        let mut threshold = 0;
+        if auth_contexts.is_empty() {
+            threshold = Self::class_threshold(env, account, OperationClass::Standard)?;
+        }
+        for context in auth_contexts.iter() {
+            let class = Self::operation_class(env, account, &context);
+            threshold = threshold.max(Self::class_threshold(env, account, class)?);
+        }
+
+        let mut signers = Vec::new(env);
+        for proof in proofs.iter() {
+            signers.push_back(Self::proof_signer(&proof));
+        }
+        if Self::approval_weight(env, account, &signers)? < threshold as u64 {
+            return Err(AAError::ThresholdNotMet);
+        }
+
+        for proof in proofs.iter() {
+            match proof {
+                SignerProof::Address(address) => {
+                    address.require_auth_for_args(vec![env, signature_payload.to_bytes().into_val(env)])
+                },
+                SignerProof::Passkey(passkey) => Self::verify_webauthn(env, signature_payload, &passkey)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Classify an authorized call
+    fn operation_class(env: &Env, account: &Address, context: &Context) -> OperationClass {
+        match context {
+            Context::Contract(c) => Self::call_class(env, account, &c.contract, &c.fn_name),
+            _ => OperationClass::Standard,
+        }
+    }
+
+    /// Calls into the wallet itself are Admin, TokenVault `retrieve_token` is Read
+    fn call_class(env: &Env, account: &Address, contract: &Address, function: &Symbol) -> OperationClass {
+        if contract == account {
+            OperationClass::Admin
+        } else if Self::get_token_vault(env.clone()).as_ref() == Some(contract)
+            && *function == Symbol::new(env, VAULT_READ_FUNCTION)
+        {
+            OperationClass::Read
+        } else {
+            OperationClass::Standard
+        }
+    }
+
+    /// Weight of one of the user's signers; fails for non-signers
+    fn signer_weight(env: &Env, user: &Address, signer: &Signer) -> Result<u32, AAError> {
+        let signers: Map<Signer, u32> = env.storage().persistent()
+            .get(&DataKey::Signers(user.clone()))
+            .ok_or(AAError::MultisigNotConfigured)?;
+        signers.get(signer.clone()).ok_or(AAError::UnknownSigner)
+    }
+
+    /// Signer a proof is made by
+    fn proof_signer(proof: &SignerProof) -> Signer {
+        match proof {
+            SignerProof::Address(address) => Signer::Address(address.clone()),
+            SignerProof::Passkey(passkey) => Signer::Passkey(passkey.public_key.clone()),
+        }
+    }
+
+    /// Check one signer's approval of `args`: an address authorizes them, a passkey signs
+    /// sha256(XDR of `(this contract, function, args)`) as its WebAuthn challenge
+    fn require_proof(env: &Env, proof: &SignerProof, function: Symbol, args: Vec<Val>) -> Result<(), AAError> {
+        match proof {
+            SignerProof::Address(address) => address.require_auth_for_args(args),
+            SignerProof::Passkey(passkey) => {
+                let message = (env.current_contract_address(), function, args).to_xdr(env);
+                Self::verify_webauthn(env, &env.crypto().sha256(&message), passkey)?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Load a proposal that can still be approved or executed
+    fn pending_proposal(env: &Env, proposal_id: u64) -> Result<Proposal, AAError> {
+        let proposal: Proposal = env.storage().persistent()
+            .get(&DataKey::Proposal(proposal_id))
+            .ok_or(AAError::ProposalNotFound)?;
+
+        if proposal.status != ProposalStatus::Pending {
+            return Err(AAError::ProposalNotPending);
+        }
+        if env.ledger().timestamp() >= proposal.expires_at {
+            return Err(AAError::ProposalExpired);
+        }
+
+        Ok(proposal)
+    }
+
+    /// Weight threshold for an operation class
+    fn class_threshold(env: &Env, user: &Address, class: OperationClass) -> Result<u32, AAError> {
+        env.storage().persistent()
+            .get(&DataKey::ClassThreshold(user.clone(), class))
+            .or_else(|| env.storage().persistent().get(&DataKey::Threshold(user.clone())))
+            .ok_or(AAError::MultisigNotConfigured)
+    }
+
+    /// Require each distinct approver to be a signer approving `args`, with total weight >= threshold
+    fn check_approvals(
+        env: &Env,
+        user: &Address,
+        approvers: &Vec<SignerProof>,
+        function: Symbol,
+        args: Vec<Val>,
+        threshold: u32,
+    ) -> Result<(), AAError> {
+        let mut signers = Vec::new(env);
+        for approver in approvers.iter() {
+            signers.push_back(Self::proof_signer(&approver));
+        }
+        if Self::approval_weight(env, user, &signers)? < threshold as u64 {
+            return Err(AAError::ThresholdNotMet);
+        }
+
+        for approver in approvers.iter() {
+            Self::require_proof(env, &approver, function.clone(), args.clone())?;
+        }
+
+        Ok(())
+    }
+
+    /// Total weight of the given signers; rejects non-signers and repeats
+    fn approval_weight(env: &Env, user: &Address, signers: &Vec<Signer>) -> Result<u64, AAError> {
+        let valid_signers: Map<Signer, u32> = env.storage().persistent()
+            .get(&DataKey::Signers(user.clone()))
+            .ok_or(AAError::MultisigNotConfigured)?;
+
+        let mut counted: Vec<Signer> = Vec::new(env);
+        let mut weight = 0u64;
+        for signer in signers.iter() {
+            let signer_weight = valid_signers.get(signer.clone()).ok_or(AAError::UnknownSigner)?;
+            if counted.contains(&signer) {
+                return Err(AAError::DuplicateSigner);
+            }
+            weight += signer_weight as u64;
+            counted.push_back(signer);
+        }
+
+        Ok(weight)
+    }
+
+    /// Apply a signer change once the current signers approve `(user, change, nonce)` at the
+    /// Admin threshold; the user's nonce is consumed so approvals cannot be replayed
+    /// Removals stay available while paused so a compromised signer can be cut off
+    fn change_signers(env: &Env, user: &Address, change: SignerChange, approvers: &Vec<SignerProof>) -> Result<(), AAError> {
+        if !matches!(change, SignerChange::Remove(_)) {
+            Self::require_not_paused(env)?;
+        }
+        Self::require_account(env, user)?;
+
+        let nonce = Self::get_nonce(env.clone(), user.clone());
+        let approval_args = vec![env, user.into_val(env), change.clone().into_val(env), nonce.into_val(env)];
+        let threshold = Self::class_threshold(env, user, OperationClass::Admin)?;
+        Self::check_approvals(env, user, approvers, symbol_short!("signers"), approval_args, threshold)?;
+        Self::consume_nonce(env, user, nonce)?;
+
+        let (mut signers, mut threshold) = Self::get_multisig(env.clone(), user.clone())
+            .ok_or(AAError::MultisigNotConfigured)?;
+        match change.clone() {
+            SignerChange::Add(signer, weight) => {
+                if signers.contains_key(signer.clone()) {
+                    return Err(AAError::SignerExists);
+                }
+                signers.set(signer, weight);
+            },
+            SignerChange::Remove(signer) => {
+                if signers.remove(signer).is_none() {
+                    return Err(AAError::UnknownSigner);
+                }
+            },
+            SignerChange::ChangeThreshold(new_threshold) => threshold = new_threshold,
+            SignerChange::Replace(old_signer, new_signer) => {
+                let weight = signers.get(old_signer.clone()).ok_or(AAError::UnknownSigner)?;
+                if signers.contains_key(new_signer.clone()) {
+                    return Err(AAError::SignerExists);
+                }
+                signers.remove(old_signer);
+                signers.set(new_signer, weight);
+            },
+            SignerChange::SetWeight(signer, weight) => {
+                if !signers.contains_key(signer.clone()) {
+                    return Err(AAError::UnknownSigner);
+                }
+                signers.set(signer, weight);
+            },
+            SignerChange::SetClassThreshold(class, class_threshold) => {
+                env.storage().persistent().set(&DataKey::ClassThreshold(user.clone(), class), &class_threshold);
+            },
+        }
+
+        Self::validate_signers(env, user, &signers, threshold)?;
+
+        env.storage().persistent().set(&DataKey::Signers(user.clone()), &signers);
+        env.storage().persistent().set(&DataKey::Threshold(user.clone()), &threshold);
+
+        publish(env,
+            (symbol_short!("ms_change"), user.clone()),
+            (change, threshold, signers.len())
+        );
+        Ok(())
+    }
+
+    /// Enforce non-zero weights and 1 <= threshold <= total weight for every class
+    fn validate_signers(env: &Env, user: &Address, signers: &Map<Signer, u32>, threshold: u32) -> Result<(), AAError> {
+        let mut total_weight = 0u64;
+        for (_, weight) in signers.iter() {
+            if weight == 0 {
+                return Err(AAError::InvalidSignerWeight);
+            }
+            total_weight += weight as u64;
+        }
+
+        let mut thresholds = vec![env, threshold];
+        for class in [OperationClass::Read, OperationClass::Standard, OperationClass::Admin] {
+            if let Some(class_threshold) = env.storage().persistent().get(&DataKey::ClassThreshold(user.clone(), class)) {
+                thresholds.push_back(class_threshold);
+            }
+        }
+
+        for threshold in thresholds.iter() {
+            if threshold == 0 || threshold as u64 > total_weight {
+                return Err(AAError::InvalidThreshold);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Publish a (topics, data) event; the one place the deprecated untyped event API is used
+#[allow(deprecated)]
+fn publish<T: Topics, D: IntoVal<Env, Val>>(env: &Env, topics: T, data: D) {
+    env.events().publish(topics, data);
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,1620 @@
+#![cfg(test)]
+extern crate std;
+
+use super::*;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use ed25519_dalek::{Signer as _, SigningKey};
+use p256::ecdsa::{signature::hazmat::PrehashSigner, Signature as P256Signature, SigningKey as P256SigningKey};
+use soroban_sdk::{
+    auth::ContractContext,
+    token::{StellarAssetClient, TokenClient},
+    testutils::{Address as _, AuthorizedFunction, Events, Ledger, MockAuth, MockAuthInvoke},
+    xdr::ToXdr,
+    map, Env, IntoVal,
+};
+
+/// Fee bound signed with meta-transactions that do not exercise it
+const MAX_FEE: i128 = 100_000;
+
+#[contract]
+struct Counter;
+
+#[contractimpl]
+impl Counter {
+    pub fn add(env: Env, amount: i128) -> i128 {
+        let total: i128 = env.storage().instance().get(&symbol_short!("total")).unwrap_or(0) + amount;
+        env.storage().instance().set(&symbol_short!("total"), &total);
+        total
+    }
+
+    pub fn fail(_env: Env) {
+        panic!("Counter failure");
+    }
+}
+
+fn setup(env: &Env) -> (Address, AccountAbstractionClient<'_>, StellarAssetClient<'_>) {
+    let contract_id = env.register(AccountAbstraction, ());
+    let client = AccountAbstractionClient::new(env, &contract_id);
+
+    let owner = Address::generate(env);
+    let gas_token = env.register_stellar_asset_contract_v2(owner.clone());
+    client.initialize(&owner, &gas_token.address());
+
+    (contract_id, client, StellarAssetClient::new(env, &gas_token.address()))
+}
+
+fn address_proofs(env: &Env, signers: &[&Address]) -> Vec<SignerProof> {
+    let mut proofs = Vec::new(env);
+    for signer in signers {
+        proofs.push_back(SignerProof::Address((*signer).clone()));
+    }
+    proofs
+}
+
+fn sign_payload(env: &Env, key: &SigningKey, payload: &SessionPayload) -> BytesN<64> {
+    let xdr = payload.clone().to_xdr(env);
+    let mut message = [0u8; 512];
+    xdr.copy_into_slice(&mut message[..xdr.len() as usize]);
+    BytesN::from_array(env, &key.sign(&message[..xdr.len() as usize]).to_bytes())
+}
+
+fn passkey_public_key(env: &Env, key: &P256SigningKey) -> BytesN<65> {
+    BytesN::from_array(env, key.verifying_key().to_encoded_point(false).as_bytes().try_into().unwrap())
+}
+
+/// WebAuthn assertion over `challenge` as a browser authenticator would produce it
+fn passkey_sign(env: &Env, key: &P256SigningKey, challenge: &[u8; 32]) -> WebAuthnSignature {
+    let mut authenticator_data = [0u8; 37];
+    authenticator_data[32] = 0x05; // User present, user verified
+    let client_data_json = std::format!(
+        r#"{{"type":"webauthn.get","challenge":"{}","origin":"https://app.tychee.io","crossOrigin":false}}"#,
+        URL_SAFE_NO_PAD.encode(challenge)
+    );
+
+    let authenticator_data = Bytes::from_array(env, &authenticator_data);
+    let client_data_json = Bytes::from_slice(env, client_data_json.as_bytes());
+    let mut message = authenticator_data.clone();
+    message.extend_from_array(&env.crypto().sha256(&client_data_json).to_array());
+    let digest = env.crypto().sha256(&message).to_array();
+
+    let signature: P256Signature = key.sign_prehash(&digest).unwrap();
+    let signature = signature.normalize_s().unwrap_or(signature);
+
+    WebAuthnSignature {
+        public_key: passkey_public_key(env, key),
+        authenticator_data,
+        client_data_json,
+        signature: BytesN::from_array(env, &signature.to_bytes().into()),
+    }
+}
+
+/// Passkey approval of `args` for an AA entry point, signed over its `require_proof` challenge
+fn passkey_approval(env: &Env, contract_id: &Address, key: &P256SigningKey, function: &str, args: Vec<Val>) -> SignerProof {
+    let message = (contract_id.clone(), Symbol::new(env, function), args).to_xdr(env);
+    SignerProof::Passkey(passkey_sign(env, key, &env.crypto().sha256(&message).to_array()))
+}
+
+#[test]
+fn test_check_auth_owner() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, _client, _gas_token) = setup(&env);
+    let payload = BytesN::from_array(&env, &[1u8; 32]);
+
+    let result = env.try_invoke_contract_check_auth::<AAError>(
+        &contract_id,
+        &payload,
+        AccountSignature::Owner.into_val(&env),
+        &Vec::new(&env),
+    );
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_check_auth_session_key() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, _gas_token) = setup(&env);
+
+    let session_key = SigningKey::from_bytes(&[3u8; 32]);
+    let public_key = BytesN::from_array(&env, &session_key.verifying_key().to_bytes());
+
+    // The wallet's own address is the configured user
+    client.add_session_key(&contract_id, &public_key, &3600, &Vec::new(&env));
+
+    let payload = [1u8; 32];
+    let session = SessionSignature {
+        public_key,
+        signature: BytesN::from_array(&env, &session_key.sign(&payload).to_bytes()),
+    };
+
+    let result = env.try_invoke_contract_check_auth::<AAError>(
+        &contract_id,
+        &BytesN::from_array(&env, &payload),
+        AccountSignature::SessionKey(session.clone()).into_val(&env),
+        &Vec::new(&env),
+    );
+    assert!(result.is_ok());
+
+    // Expired session keys are rejected
+    env.ledger().with_mut(|li| {
+        li.timestamp += 3600;
+    });
+    let result = env.try_invoke_contract_check_auth::<AAError>(
+        &contract_id,
+        &BytesN::from_array(&env, &payload),
+        AccountSignature::SessionKey(session).into_val(&env),
+        &Vec::new(&env),
+    );
+    assert_eq!(result.err(), Some(Ok(AAError::InvalidSessionKey)));
+}
+
+#[test]
+fn test_check_auth_multisig() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, _gas_token) = setup(&env);
+
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+    let c = Address::generate(&env);
+    client.setup_multisig(&contract_id, &map![&env, (Signer::Address(a.clone()), 1), (Signer::Address(b.clone()), 1), (Signer::Address(c.clone()), 1)], &2, &Map::new(&env));
+
+    let payload = BytesN::from_array(&env, &[1u8; 32]);
+    let check = |signature: AccountSignature| {
+        env.try_invoke_contract_check_auth::<AAError>(
+            &contract_id,
+            &payload,
+            signature.into_val(&env),
+            &Vec::new(&env),
+        )
+    };
+
+    assert!(check(AccountSignature::MultiSig(address_proofs(&env, &[&a, &c]))).is_ok());
+    assert_eq!(check(AccountSignature::MultiSig(address_proofs(&env, &[&a]))).err(), Some(Ok(AAError::ThresholdNotMet)));
+    assert_eq!(check(AccountSignature::MultiSig(address_proofs(&env, &[&a, &a]))).err(), Some(Ok(AAError::DuplicateSigner)));
+    assert_eq!(
+        check(AccountSignature::MultiSig(address_proofs(&env, &[&a, &Address::generate(&env)]))).err(),
+        Some(Ok(AAError::UnknownSigner))
+    );
+
+    // Owner alone cannot bypass multisig
+    assert_eq!(check(AccountSignature::Owner).err(), Some(Ok(AAError::SignatureNotAllowed)));
+}
+
+#[test]
+fn test_verify_session_key_signature() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, _gas_token) = setup(&env);
+    let user = Address::generate(&env);
+
+    let session_key = SigningKey::from_bytes(&[5u8; 32]);
+    let public_key = BytesN::from_array(&env, &session_key.verifying_key().to_bytes());
+    client.add_session_key(&user, &public_key, &3600, &Vec::new(&env));
+
+    let payload = SessionPayload {
+        user: user.clone(),
+        verifier: contract_id.clone(),
+        contract: Address::generate(&env),
+        function: Symbol::new(&env, "retrieve_token"),
+        args_hash: BytesN::from_array(&env, &[0u8; 32]),
+        max_fee: MAX_FEE,
+        nonce: client.get_nonce(&user),
+        expires_at: env.ledger().timestamp() + 60,
+    };
+    let signature = sign_payload(&env, &session_key, &payload);
+
+    // Verification is read-only and leaves the nonce untouched
+    assert!(client.verify_session_key(&user, &public_key, &payload, &signature));
+    assert!(client.verify_session_key(&user, &public_key, &payload, &signature));
+    assert_eq!(client.get_nonce(&user), 0);
+
+    // A payload signed for another account or contract is rejected
+    let other = Address::generate(&env);
+    client.add_session_key(&other, &public_key, &3600, &Vec::new(&env));
+    assert!(!client.verify_session_key(&other, &public_key, &payload, &signature));
+    let mut foreign = payload.clone();
+    foreign.verifier = Address::generate(&env);
+    let foreign_signature = sign_payload(&env, &session_key, &foreign);
+    assert!(!client.verify_session_key(&user, &public_key, &foreign, &foreign_signature));
+
+    // A stale nonce is rejected
+    let mut stale = payload.clone();
+    stale.nonce = 1;
+    assert!(!client.verify_session_key(&user, &public_key, &stale, &sign_payload(&env, &session_key, &stale)));
+
+    // Knowing the public key alone is not enough
+    let mut next = payload.clone();
+    next.expires_at += 1;
+    assert!(client.try_verify_session_key(&user, &public_key, &next, &signature).is_err());
+}
+
+#[test]
+fn test_check_auth_session_scope() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, _gas_token) = setup(&env);
+
+    let token = Address::generate(&env);
+    let session_key = SigningKey::from_bytes(&[3u8; 32]);
+    let public_key = BytesN::from_array(&env, &session_key.verifying_key().to_bytes());
+    let scope = SessionScope {
+        contract: token.clone(),
+        function: Symbol::new(&env, "transfer"),
+        arg_limits: vec![&env, ArgLimit { index: 2, max: 500 }],
+    };
+    client.add_session_key(&contract_id, &public_key, &3600, &vec![&env, scope]);
+
+    let payload = [1u8; 32];
+    let signature = AccountSignature::SessionKey(SessionSignature {
+        public_key,
+        signature: BytesN::from_array(&env, &session_key.sign(&payload).to_bytes()),
+    });
+    let check = |contract: &Address, fn_name: &str, amount: i128| {
+        let context = Context::Contract(ContractContext {
+            contract: contract.clone(),
+            fn_name: Symbol::new(&env, fn_name),
+            args: vec![&env, contract_id.into_val(&env), token.into_val(&env), amount.into_val(&env)],
+        });
+        env.try_invoke_contract_check_auth::<AAError>(
+            &contract_id,
+            &BytesN::from_array(&env, &payload),
+            signature.clone().into_val(&env),
+            &vec![&env, context],
+        )
+    };
+
+    assert!(check(&token, "transfer", 500).is_ok());
+    assert_eq!(check(&token, "transfer", 501).err(), Some(Ok(AAError::OutOfScope)));
+    assert_eq!(check(&token, "approve", 1).err(), Some(Ok(AAError::OutOfScope)));
+    assert_eq!(check(&Address::generate(&env), "transfer", 1).err(), Some(Ok(AAError::OutOfScope)));
+}
+
+#[test]
+fn test_execute_metatx_session_scope() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let user = contract_id.clone();
+    let counter = env.register(Counter, ());
+
+    let session_key = SigningKey::from_bytes(&[5u8; 32]);
+    let public_key = BytesN::from_array(&env, &session_key.verifying_key().to_bytes());
+    let scope = SessionScope {
+        contract: counter.clone(),
+        function: Symbol::new(&env, "add"),
+        arg_limits: vec![&env, ArgLimit { index: 0, max: 10 }],
+    };
+    let sponsor = Address::generate(&env);
+    client.set_sponsor(&user, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+    client.add_session_key(&user, &public_key, &3600, &vec![&env, scope]);
+
+    let deadline = env.ledger().timestamp() + 60;
+    let auth_for = |function: &str, args: &Vec<Val>| {
+        let payload = SessionPayload {
+            user: user.clone(),
+            verifier: contract_id.clone(),
+            contract: counter.clone(),
+            function: Symbol::new(&env, function),
+            args_hash: env.crypto().sha256(&args.clone().to_xdr(&env)).to_bytes(),
+            max_fee: MAX_FEE,
+            nonce: client.get_nonce(&user),
+            expires_at: deadline,
+        };
+        Some(SessionAuth {
+            public_key: public_key.clone(),
+            signature: sign_payload(&env, &session_key, &payload),
+        })
+    };
+
+    let add = Symbol::new(&env, "add");
+    let args = vec![&env, 7i128.into_val(&env)];
+    let result = client.execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &args, &0, &deadline, &auth_for("add", &args));
+    assert_eq!(i128::try_from_val(&env, &result).unwrap(), 7);
+    assert_eq!(client.get_nonce(&user), 1);
+
+    // Amount above the scope limit
+    let args = vec![&env, 11i128.into_val(&env)];
+    let result = client.try_execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &args, &1, &deadline, &auth_for("add", &args));
+    assert_eq!(result.err(), Some(Ok(AAError::OutOfScope)));
+
+    // Function outside the scope
+    let args = Vec::new(&env);
+    let result = client.try_execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &Symbol::new(&env, "fail"), &args, &1, &deadline, &auth_for("fail", &args));
+    assert_eq!(result.err(), Some(Ok(AAError::OutOfScope)));
+}
+
+#[test]
+fn test_execute_metatx_dispatch() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let user = contract_id.clone();
+    let sponsor = Address::generate(&env);
+    let counter = env.register(Counter, ());
+
+    client.set_sponsor(&user, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    let add = Symbol::new(&env, "add");
+    let deadline = env.ledger().timestamp() + 60;
+    client.execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &vec![&env, 2i128.into_val(&env)], &0, &deadline, &None);
+    let result = client.execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &vec![&env, 3i128.into_val(&env)], &1, &deadline, &None);
+    assert_eq!(i128::try_from_val(&env, &result).unwrap(), 5);
+    assert_eq!(client.get_sponsor_pool(&sponsor), SponsorPool { balance: 100000 - 2000, spent: 2000 });
+
+    // A failing callee leaves the gas pool and nonce untouched
+    let result = client.try_execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &Symbol::new(&env, "fail"), &Vec::new(&env), &2, &deadline, &None);
+    assert_eq!(result.err(), Some(Ok(AAError::CallFailed)));
+    assert_eq!(client.get_sponsor_pool(&sponsor).balance, 100000 - 2000);
+    assert_eq!(client.get_nonce(&user), 2);
+}
+
+#[test]
+fn test_execute_metatx_replay_protection() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let user = contract_id.clone();
+    let sponsor = Address::generate(&env);
+    let counter = env.register(Counter, ());
+
+    client.set_sponsor(&user, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    let add = Symbol::new(&env, "add");
+    let args = vec![&env, 1i128.into_val(&env)];
+    let deadline = env.ledger().timestamp() + 60;
+    client.execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &args, &0, &deadline, &None);
+
+    // The user authorized exactly this call, nonce and deadline
+    let (auth_address, invocation) = env.auths().into_iter().find(|(address, _)| *address == user).unwrap();
+    assert_eq!(auth_address, user);
+    assert_eq!(
+        invocation.function,
+        AuthorizedFunction::Contract((
+            contract_id.clone(),
+            Symbol::new(&env, "execute_metatx"),
+            (counter.clone(), add.clone(), args.clone(), MAX_FEE, 0u64, deadline).into_val(&env),
+        ))
+    );
+
+    // Replaying the same nonce fails
+    let result = client.try_execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &args, &0, &deadline, &None);
+    assert_eq!(result.err(), Some(Ok(AAError::InvalidNonce)));
+
+    // Past the deadline
+    env.ledger().with_mut(|li| {
+        li.timestamp = deadline + 1;
+    });
+    let result = client.try_execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &args, &1, &deadline, &None);
+    assert_eq!(result.err(), Some(Ok(AAError::SignatureExpired)));
+}
+
+#[test]
+fn test_execute_metatx_requires_user_auth() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let user = contract_id.clone();
+    let sponsor = Address::generate(&env);
+    let relayer = Address::generate(&env);
+    let counter = env.register(Counter, ());
+
+    client.set_sponsor(&user, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    // Relayer submits without the user's authorization
+    env.set_auths(&[]);
+    let add = Symbol::new(&env, "add");
+    let deadline = env.ledger().timestamp() + 60;
+    let result = client.try_execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &vec![&env, 1i128.into_val(&env)], &0, &deadline, &None);
+    assert!(result.is_err());
+    assert_eq!(client.get_sponsor_pool(&sponsor).balance, 100000);
+}
+
+#[test]
+fn test_execute_metatx_cannot_drain_pools() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let sponsor = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    let usdc = env.register_stellar_asset_contract_v2(Address::generate(&env)).address();
+    client.set_fee_token_rate(&usdc, &5_000_000);
+
+    let transfer = Symbol::new(&env, "transfer");
+    let args = vec![&env, contract_id.into_val(&env), attacker.into_val(&env), 100000i128.into_val(&env)];
+    let deadline = env.ledger().timestamp() + 60;
+
+    // A stranger sponsoring themselves cannot borrow the contract's authority
+    client.set_sponsor(&attacker, &sponsor);
+    assert_eq!(
+        client.try_execute_metatx(&attacker, &relayer, &0, &MAX_FEE, &gas_token.address, &transfer, &args, &0, &deadline, &None).err(),
+        Some(Ok(AAError::NotOwnAccount))
+    );
+    let calls = vec![&env, Call { contract: gas_token.address.clone(), function: transfer.clone(), args: args.clone() }];
+    assert_eq!(
+        client.try_execute_batch(&attacker, &relayer, &0, &MAX_FEE, &calls, &0, &deadline, &None).err(),
+        Some(Ok(AAError::NotOwnAccount))
+    );
+
+    // Nor can the account itself reach the gas token or the contract
+    client.set_sponsor(&contract_id, &sponsor);
+    for target in [gas_token.address.clone(), contract_id.clone()] {
+        assert_eq!(
+            client.try_execute_metatx(&contract_id, &relayer, &0, &MAX_FEE, &target, &transfer, &args, &0, &deadline, &None).err(),
+            Some(Ok(AAError::ReservedTarget))
+        );
+    }
+    assert_eq!(TokenClient::new(&env, &gas_token.address).balance(&contract_id), 100000);
+    assert_eq!(client.get_sponsor_pool(&sponsor).balance, 100000);
+
+    // Its own paymaster token stays spendable
+    StellarAssetClient::new(&env, &usdc).mint(&contract_id, &500);
+    let pay = vec![&env, contract_id.into_val(&env), attacker.into_val(&env), 500i128.into_val(&env)];
+    client.execute_metatx(&contract_id, &relayer, &0, &MAX_FEE, &usdc, &transfer, &pay, &0, &deadline, &None);
+    assert_eq!(TokenClient::new(&env, &usdc).balance(&attacker), 500);
+}
+
+#[test]
+fn test_check_auth_cannot_move_pooled_gas() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let sponsor = Address::generate(&env);
+    let thief = Address::generate(&env);
+    let signer = Address::generate(&env);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    let transfer = Symbol::new(&env, "transfer");
+    let session_key = SigningKey::from_bytes(&[3u8; 32]);
+    let public_key = BytesN::from_array(&env, &session_key.verifying_key().to_bytes());
+    let scope = SessionScope { contract: gas_token.address.clone(), function: transfer.clone(), arg_limits: Vec::new(&env) };
+    client.add_session_key(&contract_id, &public_key, &3600, &vec![&env, scope]);
+
+    let payload = [1u8; 32];
+    let context = Context::Contract(ContractContext {
+        contract: gas_token.address.clone(),
+        fn_name: transfer,
+        args: vec![&env, contract_id.into_val(&env), thief.into_val(&env), 100000i128.into_val(&env)],
+    });
+    let check = |signature: AccountSignature| {
+        env.try_invoke_contract_check_auth::<AAError>(
+            &contract_id,
+            &BytesN::from_array(&env, &payload),
+            signature.into_val(&env),
+            &vec![&env, context.clone()],
+        )
+    };
+
+    // Neither the owner, a session key scoped to the gas token nor the signers can spend the pools
+    assert_eq!(check(AccountSignature::Owner).err(), Some(Ok(AAError::ReservedTarget)));
+    let session = SessionSignature {
+        public_key,
+        signature: BytesN::from_array(&env, &session_key.sign(&payload).to_bytes()),
+    };
+    assert_eq!(check(AccountSignature::SessionKey(session)).err(), Some(Ok(AAError::ReservedTarget)));
+    client.setup_multisig(&contract_id, &map![&env, (Signer::Address(signer.clone()), 1)], &1, &Map::new(&env));
+    assert_eq!(
+        check(AccountSignature::MultiSig(address_proofs(&env, &[&signer]))).err(),
+        Some(Ok(AAError::ReservedTarget))
+    );
+
+    assert_eq!(client.get_pool_reconciliation().surplus, 0);
+}
+
+#[test]
+fn test_sponsor_pools() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let cheap_sponsor = Address::generate(&env);
+    let rich_sponsor = Address::generate(&env);
+    let counter = env.register(Counter, ());
+
+    gas_token.mint(&cheap_sponsor, &500);
+    client.fund_gas_pool(&cheap_sponsor, &500);
+    gas_token.mint(&rich_sponsor, &5000);
+    client.fund_gas_pool(&rich_sponsor, &5000);
+    client.set_sponsor(&contract_id, &cheap_sponsor);
+    assert_eq!(client.get_gas_pool(), 5500);
+
+    let add = Symbol::new(&env, "add");
+    let args = vec![&env, 1i128.into_val(&env)];
+    let deadline = env.ledger().timestamp() + 60;
+
+    // The current sponsor cannot cover the cost even though other pools can
+    let result = client.try_execute_metatx(&contract_id, &relayer, &0, &MAX_FEE, &counter, &add, &args, &0, &deadline, &None);
+    assert_eq!(result.err(), Some(Ok(AAError::InsufficientGasPool)));
+
+    client.set_sponsor(&contract_id, &rich_sponsor);
+    client.execute_metatx(&contract_id, &relayer, &0, &MAX_FEE, &counter, &add, &args, &0, &deadline, &None);
+    assert_eq!(client.get_sponsor_pool(&rich_sponsor), SponsorPool { balance: 4000, spent: 1000 });
+    assert_eq!(client.get_sponsor_pool(&cheap_sponsor), SponsorPool { balance: 500, spent: 0 });
+
+    // A sponsor cannot attach itself to an account without the account's consent
+    env.mock_auths(&[MockAuth {
+        address: &cheap_sponsor,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "set_sponsor",
+            args: (contract_id.clone(), cheap_sponsor.clone()).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(client.try_set_sponsor(&contract_id, &cheap_sponsor).is_err());
+    assert_eq!(client.get_sponsor_allowance(&contract_id).unwrap().sponsor, rich_sponsor);
+    env.mock_all_auths();
+
+    client.withdraw_gas_pool(&rich_sponsor, &4000);
+    assert_eq!(client.get_sponsor_pool(&rich_sponsor).balance, 0);
+    assert_eq!(client.get_gas_pool(), 500);
+
+    // Every movement is backed by token transfers
+    let token = TokenClient::new(&env, &gas_token.address);
+    assert_eq!(token.balance(&rich_sponsor), 4000);
+    assert_eq!(token.balance(&relayer), 1000);
+    assert_eq!(client.get_pool_reconciliation(), PoolReconciliation { accounted: 500, balance: 500, surplus: 0 });
+    assert!(client.try_withdraw_gas_pool(&cheap_sponsor, &501).is_err());
+}
+
+#[test]
+fn test_sponsor_policy_limits() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let user = contract_id.clone();
+    let sponsor = Address::generate(&env);
+    let relayer = Address::generate(&env);
+    let counter = env.register(Counter, ());
+    let other = env.register(Counter, ());
+
+    client.set_sponsor(&user, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    let now = env.ledger().timestamp();
+    client.set_sponsor_policy(&user, &SponsorPolicy {
+        max_spend: Some(3000),
+        tx_quota: Some(2),
+        period: 86400,
+        allowed_targets: vec![&env, counter.clone()],
+        expires_at: Some(now + 7 * 86400),
+    });
+
+    let add = Symbol::new(&env, "add");
+    let args = vec![&env, 1i128.into_val(&env)];
+    let deadline = now + 30 * 86400;
+    let run = |target: &Address, nonce: u64| client.try_execute_metatx(&user, &relayer, &0, &MAX_FEE, target, &add, &args, &nonce, &deadline, &None);
+
+    assert_eq!(run(&other, 0).err(), Some(Ok(AAError::TargetNotAllowed)));
+    assert!(run(&counter, 0).is_ok());
+    assert!(run(&counter, 1).is_ok());
+    assert_eq!(run(&counter, 2).err(), Some(Ok(AAError::TxQuotaExceeded)));
+
+    let allowance = client.get_sponsor_allowance(&user).unwrap();
+    assert_eq!(allowance.remaining_spend, Some(1000));
+    assert_eq!(allowance.remaining_tx, Some(0));
+
+    // Quota resets with the next period, spend cap does not
+    env.ledger().with_mut(|li| {
+        li.timestamp += 86400;
+    });
+    assert!(run(&counter, 2).is_ok());
+    assert_eq!(run(&counter, 3).err(), Some(Ok(AAError::SpendLimitExceeded)));
+
+    env.ledger().with_mut(|li| {
+        li.timestamp += 7 * 86400;
+    });
+    assert_eq!(run(&counter, 3).err(), Some(Ok(AAError::SponsorshipExpired)));
+
+    // Revoked sponsorship leaves the user unsponsored
+    client.revoke_sponsorship(&user);
+    assert!(client.get_sponsor_allowance(&user).is_none());
+    assert!(!client.get_features(&user).sponsored);
+}
+
+#[test]
+fn test_session_key_lifecycle() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_contract_id, client, _gas_token) = setup(&env);
+    let user = Address::generate(&env);
+
+    let short_lived = BytesN::from_array(&env, &[1u8; 32]);
+    let long_lived = BytesN::from_array(&env, &[2u8; 32]);
+    client.add_session_key(&user, &short_lived, &60, &Vec::new(&env));
+    client.add_session_key(&user, &long_lived, &3600, &Vec::new(&env));
+    assert_eq!(client.list_session_keys(&user).len(), 2);
+
+    env.ledger().with_mut(|li| {
+        li.timestamp += 60;
+    });
+    let keys = client.list_session_keys(&user);
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys.get(0).unwrap().key, long_lived);
+
+    // Expired keys are pruned on the next write
+    client.add_session_key(&user, &BytesN::from_array(&env, &[3u8; 32]), &3600, &Vec::new(&env));
+    assert!(client.try_revoke_session_key(&user, &short_lived).is_err());
+
+    client.revoke_session_key(&user, &long_lived);
+    let keys = client.list_session_keys(&user);
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys.get(0).unwrap().key, BytesN::from_array(&env, &[3u8; 32]));
+}
+
+#[test]
+fn test_session_key_cap() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_contract_id, client, _gas_token) = setup(&env);
+    let user = Address::generate(&env);
+
+    for i in 0..MAX_SESSION_KEYS as u8 {
+        client.add_session_key(&user, &BytesN::from_array(&env, &[i; 32]), &3600, &Vec::new(&env));
+    }
+    assert_eq!(
+        client.try_add_session_key(&user, &BytesN::from_array(&env, &[0xff; 32]), &3600, &Vec::new(&env)).err(),
+        Some(Ok(AAError::TooManySessionKeys))
+    );
+}
+
+#[test]
+fn test_multisig_signer_changes() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_contract_id, client, _gas_token) = setup(&env);
+    let user = Address::generate(&env);
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+    let c = Address::generate(&env);
+    let d = Address::generate(&env);
+    client.setup_multisig(&user, &map![&env, (Signer::Address(a.clone()), 1), (Signer::Address(b.clone()), 1), (Signer::Address(c.clone()), 1)], &2, &Map::new(&env));
+
+    // Setup cannot be repeated to bypass the current signers
+    assert!(client.try_setup_multisig(&user, &map![&env, (Signer::Address(d.clone()), 1)], &1, &Map::new(&env)).is_err());
+
+    // Changes need the current threshold of distinct signers
+    assert_eq!(
+        client.try_add_signer(&user, &Signer::Address(d.clone()), &1, &address_proofs(&env, &[&a])).err(),
+        Some(Ok(AAError::ThresholdNotMet))
+    );
+    assert_eq!(
+        client.try_add_signer(&user, &Signer::Address(d.clone()), &1, &address_proofs(&env, &[&a, &a])).err(),
+        Some(Ok(AAError::DuplicateSigner))
+    );
+    assert_eq!(
+        client.try_add_signer(&user, &Signer::Address(d.clone()), &1, &address_proofs(&env, &[&a, &d])).err(),
+        Some(Ok(AAError::UnknownSigner))
+    );
+
+    client.add_signer(&user, &Signer::Address(d.clone()), &1, &address_proofs(&env, &[&a, &b]));
+
+    // Each approval is bound to this account and this exact change
+    let approval = AuthorizedFunction::Contract((
+        client.address.clone(),
+        Symbol::new(&env, "add_signer"),
+        vec![&env, user.into_val(&env), SignerChange::Add(Signer::Address(d.clone()), 1).into_val(&env), 0u64.into_val(&env)],
+    ));
+    let auths = env.auths();
+    assert!(auths.iter().any(|(addr, invocation)| *addr == a && invocation.function == approval));
+    assert!(auths.iter().any(|(addr, invocation)| *addr == b && invocation.function == approval));
+
+    client.change_threshold(&user, &3, &address_proofs(&env, &[&c, &d]));
+    let e = Address::generate(&env);
+    client.replace_signer(&user, &Signer::Address(a.clone()), &Signer::Address(e.clone()), &address_proofs(&env, &[&b, &c, &d]));
+    client.remove_signer(&user, &Signer::Address(b.clone()), &address_proofs(&env, &[&b, &c, &d]));
+
+    let (signers, threshold) = client.get_multisig(&user).unwrap();
+    assert_eq!(signers.len(), 3);
+    assert!(!signers.contains_key(Signer::Address(a)) && !signers.contains_key(Signer::Address(b)));
+    assert_eq!(threshold, 3);
+
+    // Removing another signer would drop below the threshold
+    let signers = address_proofs(&env, &[&c, &d, &e]);
+    let c = Signer::Address(c);
+    assert!(client.try_remove_signer(&user, &c, &signers).is_err());
+    assert!(client.try_change_threshold(&user, &0, &signers).is_err());
+    assert!(client.try_add_signer(&user, &c, &1, &signers).is_err());
+    assert!(client.try_set_signer_weight(&user, &c, &0, &signers).is_err());
+}
+
+#[test]
+fn test_verify_multisig() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_contract_id, client, _gas_token) = setup(&env);
+    let user = Address::generate(&env);
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+    let c = Address::generate(&env);
+    client.setup_multisig(&user, &map![&env, (Signer::Address(a.clone()), 1), (Signer::Address(b.clone()), 1), (Signer::Address(c.clone()), 1)], &2, &Map::new(&env));
+
+    let op_hash = BytesN::from_array(&env, &[7u8; 32]);
+    assert!(client.verify_multisig(&user, &vec![&env, a.clone(), c.clone()], &op_hash));
+
+    // Each counted signer authorized this user and operation hash
+    let approval = AuthorizedFunction::Contract((
+        client.address.clone(),
+        Symbol::new(&env, "verify_multisig"),
+        vec![&env, user.into_val(&env), op_hash.into_val(&env)],
+    ));
+    let auths = env.auths();
+    assert!(auths.iter().any(|(addr, invocation)| *addr == a && invocation.function == approval));
+    assert!(auths.iter().any(|(addr, invocation)| *addr == c && invocation.function == approval));
+
+    // Repeated or unknown signers do not count
+    assert!(!client.verify_multisig(&user, &vec![&env, a.clone(), a.clone()], &op_hash));
+    assert!(!client.verify_multisig(&user, &vec![&env, a.clone(), Address::generate(&env)], &op_hash));
+    assert!(!client.verify_multisig(&user, &vec![&env, b.clone()], &op_hash));
+
+    // Claimed signers that did not sign fail verification
+    env.set_auths(&[]);
+    assert!(client.try_verify_multisig(&user, &vec![&env, a, b], &op_hash).is_err());
+}
+
+#[test]
+fn test_weighted_multisig() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, _gas_token) = setup(&env);
+    let cfo = Address::generate(&env);
+    let clerk1 = Address::generate(&env);
+    let clerk2 = Address::generate(&env);
+    client.setup_multisig(&contract_id, &map![&env, (Signer::Address(cfo.clone()), 3), (Signer::Address(clerk1.clone()), 1), (Signer::Address(clerk2.clone()), 1)], &3, &Map::new(&env));
+
+    // Threshold is on total weight: the CFO alone, or nobody without the CFO
+    let op_hash = BytesN::from_array(&env, &[7u8; 32]);
+    assert!(client.verify_multisig(&contract_id, &vec![&env, cfo.clone()], &op_hash));
+    assert!(!client.verify_multisig(&contract_id, &vec![&env, clerk1.clone(), clerk2.clone()], &op_hash));
+
+    // Reads need one clerk, signer changes need the CFO and a clerk
+    client.set_class_threshold(&contract_id, &OperationClass::Read, &1, &address_proofs(&env, &[&cfo]));
+    client.set_class_threshold(&contract_id, &OperationClass::Admin, &4, &address_proofs(&env, &[&cfo]));
+    assert_eq!(client.get_class_threshold(&contract_id, &OperationClass::Read), Some(1));
+    assert_eq!(client.get_class_threshold(&contract_id, &OperationClass::Standard), Some(3));
+    assert_eq!(
+        client.try_add_signer(&contract_id, &Signer::Address(Address::generate(&env)), &1, &address_proofs(&env, &[&cfo])).err(),
+        Some(Ok(AAError::ThresholdNotMet))
+    );
+
+    let vault = Address::generate(&env);
+    let payload = BytesN::from_array(&env, &[1u8; 32]);
+    let check = |signers: &[&Address], contract: &Address, fn_name: &str| {
+        let context = Context::Contract(ContractContext {
+            contract: contract.clone(),
+            fn_name: Symbol::new(&env, fn_name),
+            args: Vec::new(&env),
+        });
+        env.try_invoke_contract_check_auth::<AAError>(
+            &contract_id,
+            &payload,
+            AccountSignature::MultiSig(address_proofs(&env, signers)).into_val(&env),
+            &vec![&env, context],
+        )
+    };
+
+    // Reads only use the Read class on the configured vault
+    assert_eq!(check(&[&clerk1], &vault, "retrieve_token").err(), Some(Ok(AAError::ThresholdNotMet)));
+    client.set_token_vault(&Some(vault.clone()));
+    assert_eq!(client.get_token_vault(), Some(vault.clone()));
+    assert!(check(&[&clerk1], &vault, "retrieve_token").is_ok());
+    assert_eq!(
+        check(&[&clerk1], &Address::generate(&env), "retrieve_token").err(),
+        Some(Ok(AAError::ThresholdNotMet))
+    );
+    assert_eq!(
+        check(&[&clerk1], &vault, "revoke_token").err(),
+        Some(Ok(AAError::ThresholdNotMet))
+    );
+    assert!(check(&[&cfo], &vault, "revoke_token").is_ok());
+    assert_eq!(
+        check(&[&cfo], &contract_id, "add_signer").err(),
+        Some(Ok(AAError::ThresholdNotMet))
+    );
+    assert!(check(&[&cfo, &clerk2], &contract_id, "add_signer").is_ok());
+
+    // Class thresholds must stay reachable
+    assert!(client.try_set_signer_weight(&contract_id, &Signer::Address(cfo.clone()), &1, &address_proofs(&env, &[&cfo, &clerk1])).is_err());
+
+    // Class thresholds can also be set up with the signers
+    let user = Address::generate(&env);
+    let signers = map![&env, (Signer::Address(cfo.clone()), 3), (Signer::Address(clerk1.clone()), 1)];
+    assert_eq!(
+        client.try_setup_multisig(&user, &signers, &3, &map![&env, (OperationClass::Admin, 5)]).err(),
+        Some(Ok(AAError::InvalidThreshold))
+    );
+    client.setup_multisig(&user, &signers, &3, &map![&env, (OperationClass::Read, 1), (OperationClass::Admin, 4)]);
+    assert_eq!(client.get_class_threshold(&user, &OperationClass::Read), Some(1));
+    assert_eq!(client.get_class_threshold(&user, &OperationClass::Standard), Some(3));
+    assert_eq!(client.get_class_threshold(&user, &OperationClass::Admin), Some(4));
+}
+
+#[test]
+fn test_multisig_proposals() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, _gas_token) = setup(&env);
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+    let c = Address::generate(&env);
+    client.setup_multisig(&contract_id, &map![&env, (Signer::Address(a.clone()), 1), (Signer::Address(b.clone()), 1), (Signer::Address(c.clone()), 1)], &2, &Map::new(&env));
+
+    let counter = env.register(Counter, ());
+    let add = Symbol::new(&env, "add");
+    let args = vec![&env, 5i128.into_val(&env)];
+
+    // Only signers can propose; the proposer's approval alone is below threshold
+    assert_eq!(
+        client.try_propose(&contract_id, &SignerProof::Address(Address::generate(&env)), &counter, &add, &args, &3600).err(),
+        Some(Ok(AAError::UnknownSigner))
+    );
+    let id = client.propose(&contract_id, &SignerProof::Address(a.clone()), &counter, &add, &args, &3600);
+    assert_eq!(
+        client.try_execute_proposal(&id).err(),
+        Some(Ok(AAError::ThresholdNotMet))
+    );
+    assert_eq!(client.try_approve(&id, &SignerProof::Address(a.clone())).err(), Some(Ok(AAError::DuplicateSigner)));
+
+    client.approve(&id, &SignerProof::Address(b.clone()));
+    assert_eq!(
+        env.events().all(),
+        vec![&env, (
+            client.address.clone(),
+            (symbol_short!("approved"), contract_id.clone()).into_val(&env),
+            (id, Signer::Address(b.clone()), 2u32).into_val(&env),
+        )]
+    );
+
+    let result: i128 = client.execute_proposal(&id).into_val(&env);
+    assert_eq!(result, 5);
+    assert_eq!(client.get_proposal(&id).unwrap().status, ProposalStatus::Executed);
+    assert!(client.try_execute_proposal(&id).is_err());
+
+    // Cancelled and expired proposals cannot proceed
+    let cancelled = client.propose(&contract_id, &SignerProof::Address(b.clone()), &counter, &add, &args, &3600);
+    assert_eq!(
+        client.try_cancel_proposal(&cancelled, &SignerProof::Address(a.clone())).err(),
+        Some(Ok(AAError::NotAuthorized))
+    );
+    client.cancel_proposal(&cancelled, &SignerProof::Address(b.clone()));
+    assert_eq!(client.get_proposal(&cancelled).unwrap().status, ProposalStatus::Cancelled);
+    assert!(client.try_approve(&cancelled, &SignerProof::Address(c.clone())).is_err());
+
+    let expiring = client.propose(&contract_id, &SignerProof::Address(c.clone()), &counter, &add, &args, &60);
+    env.ledger().with_mut(|li| li.timestamp += 60);
+    assert_eq!(client.try_approve(&expiring, &SignerProof::Address(a.clone())).err(), Some(Ok(AAError::ProposalExpired)));
+}
+
+#[test]
+fn test_proposals_cannot_drain_pools() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let sponsor = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    let transfer = Symbol::new(&env, "transfer");
+    let args = vec![&env, contract_id.into_val(&env), attacker.into_val(&env), 100000i128.into_val(&env)];
+
+    // A stranger's own multisig cannot propose calls made as the contract
+    client.setup_multisig(&attacker, &map![&env, (Signer::Address(attacker.clone()), 1)], &1, &Map::new(&env));
+    assert_eq!(
+        client.try_propose(&attacker, &SignerProof::Address(attacker.clone()), &gas_token.address, &transfer, &args, &3600).err(),
+        Some(Ok(AAError::NotOwnAccount))
+    );
+
+    // The account's signers cannot propose moving the pooled gas token either
+    client.setup_multisig(&contract_id, &map![&env, (Signer::Address(attacker.clone()), 1)], &1, &Map::new(&env));
+    assert_eq!(
+        client.try_propose(&contract_id, &SignerProof::Address(attacker.clone()), &gas_token.address, &transfer, &args, &3600).err(),
+        Some(Ok(AAError::ReservedTarget))
+    );
+    assert_eq!(TokenClient::new(&env, &gas_token.address).balance(&contract_id), 100000);
+}
+
+#[test]
+fn test_wallet_only_configures_own_account() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(AccountAbstraction, ());
+    let client = AccountAbstractionClient::new(&env, &contract_id);
+    let owner = Address::generate(&env);
+    let fee_admin = Address::generate(&env);
+    let gas_token = env.register_stellar_asset_contract_v2(Address::generate(&env));
+    client.initialize_wallet(&owner, &gas_token.address(), &fee_admin);
+    assert!(client.is_wallet());
+
+    // The owner cannot price the meta-txs it relays for itself at its sponsor's expense
+    let schedule = FeeSchedule {
+        base_fee: 100000,
+        call_fee: 0,
+        per_byte_fee: 0,
+        target_multipliers: Map::new(&env),
+        max_network_fee: 0,
+    };
+    env.mock_auths(&[MockAuth {
+        address: &owner,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "set_fee_schedule",
+            args: (schedule.clone(),).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(client.try_set_fee_schedule(&schedule).is_err());
+    env.mock_all_auths();
+    assert_eq!(client.get_fee_schedule().base_fee, 1000);
+
+    // Only the fee admin sets fees and rates
+    client.set_fee_schedule(&schedule);
+    assert_eq!(env.auths()[0].0, fee_admin);
+    client.set_fee_token_rate(&Address::generate(&env), &5_000_000);
+    assert_eq!(env.auths()[0].0, fee_admin);
+
+    // A stranger cannot keep account state in someone else's wallet
+    let stranger = Address::generate(&env);
+    let sponsor = Address::generate(&env);
+    let signers = map![&env, (Signer::Address(stranger.clone()), 1)];
+    assert_eq!(client.try_set_sponsor(&stranger, &sponsor).err(), Some(Ok(AAError::NotOwnAccount)));
+    assert_eq!(
+        client.try_setup_multisig(&stranger, &signers, &1, &Map::new(&env)).err(),
+        Some(Ok(AAError::NotOwnAccount))
+    );
+    assert_eq!(
+        client.try_add_session_key(&stranger, &BytesN::from_array(&env, &[1u8; 32]), &3600, &Vec::new(&env)).err(),
+        Some(Ok(AAError::NotOwnAccount))
+    );
+    assert_eq!(
+        client.try_set_feature(&stranger, &Feature::SessionKeys, &true).err(),
+        Some(Ok(AAError::NotOwnAccount))
+    );
+    assert_eq!(client.get_features(&stranger), AccountFeatures::default());
+
+    // The wallet's own account is configured as usual
+    client.set_sponsor(&contract_id, &sponsor);
+    client.setup_multisig(&contract_id, &signers, &1, &Map::new(&env));
+    let features = client.get_features(&contract_id);
+    assert!(features.sponsored && features.multisig);
+
+    // The shared deployment still serves any user
+    let (_, shared, _) = setup(&env);
+    assert!(!shared.is_wallet());
+    shared.setup_multisig(&stranger, &signers, &1, &Map::new(&env));
+}
+
+#[test]
+fn test_composable_features() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let sponsor = Address::generate(&env);
+    let relayer = Address::generate(&env);
+    let signer = Address::generate(&env);
+    let counter = env.register(Counter, ());
+    let add = Symbol::new(&env, "add");
+
+    assert_eq!(client.get_features(&contract_id), AccountFeatures::default());
+
+    // Sponsored gas, session keys and multisig active together; none overwrites another
+    let session_key = SigningKey::from_bytes(&[3u8; 32]);
+    let public_key = BytesN::from_array(&env, &session_key.verifying_key().to_bytes());
+    let scope = SessionScope { contract: counter.clone(), function: add.clone(), arg_limits: Vec::new(&env) };
+    client.setup_multisig(&contract_id, &map![&env, (Signer::Address(signer.clone()), 1)], &1, &Map::new(&env));
+    client.add_session_key(&contract_id, &public_key, &3600, &vec![&env, scope]);
+    client.set_sponsor(&contract_id, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    assert_eq!(
+        client.get_features(&contract_id),
+        AccountFeatures { sponsored: true, session_keys: true, multisig: true, paymaster: false }
+    );
+
+    // Sponsored meta-tx authorized by a session key
+    let args = vec![&env, 2i128.into_val(&env)];
+    let deadline = env.ledger().timestamp() + 60;
+    let payload = SessionPayload {
+        user: contract_id.clone(),
+        verifier: contract_id.clone(),
+        contract: counter.clone(),
+        function: add.clone(),
+        args_hash: env.crypto().sha256(&args.clone().to_xdr(&env)).to_bytes(),
+        max_fee: MAX_FEE,
+        nonce: 0,
+        expires_at: deadline,
+    };
+    let session = SessionAuth { public_key: public_key.clone(), signature: sign_payload(&env, &session_key, &payload) };
+    let result: i128 = client
+        .execute_metatx(&contract_id, &relayer, &0, &MAX_FEE, &counter, &add, &args, &0, &deadline, &Some(session))
+        .into_val(&env);
+    assert_eq!(result, 2);
+
+    // Multisig still guards the account; the owner cannot bypass it
+    let signature_payload = BytesN::from_array(&env, &[1u8; 32]);
+    let check = |signature: AccountSignature| {
+        env.try_invoke_contract_check_auth::<AAError>(
+            &contract_id,
+            &signature_payload,
+            signature.into_val(&env),
+            &Vec::new(&env),
+        )
+    };
+    assert!(check(AccountSignature::MultiSig(address_proofs(&env, &[&signer]))).is_ok());
+    assert_eq!(check(AccountSignature::Owner).err(), Some(Ok(AAError::SignatureNotAllowed)));
+
+    // Disabling one feature leaves the rest active
+    client.set_feature(&contract_id, &Feature::SessionKeys, &false);
+    assert_eq!(
+        client.get_features(&contract_id),
+        AccountFeatures { sponsored: true, session_keys: false, multisig: true, paymaster: false }
+    );
+    let session = SessionSignature {
+        public_key,
+        signature: BytesN::from_array(&env, &session_key.sign(&[1u8; 32]).to_bytes()),
+    };
+    assert_eq!(check(AccountSignature::SessionKey(session)).err(), Some(Ok(AAError::SignatureNotAllowed)));
+
+    // Features that need configuration cannot be enabled without it
+    let user = Address::generate(&env);
+    assert!(client.try_set_feature(&user, &Feature::MultiSig, &true).is_err());
+    assert!(client.try_set_feature(&user, &Feature::Sponsored, &true).is_err());
+}
+
+#[test]
+fn test_check_auth_passkey_session() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, _gas_token) = setup(&env);
+    let counter = env.register(Counter, ());
+    let add = Symbol::new(&env, "add");
+
+    let passkey = P256SigningKey::from_slice(&[9u8; 32]).unwrap();
+    let public_key = passkey_public_key(&env, &passkey);
+    let scope = SessionScope { contract: counter.clone(), function: add.clone(), arg_limits: Vec::new(&env) };
+    let key_id = client.add_passkey_session_key(&contract_id, &public_key, &3600, &vec![&env, scope]);
+
+    let listed = client.list_session_keys(&contract_id);
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed.get(0).unwrap().key, key_id);
+    assert_eq!(listed.get(0).unwrap().passkey, Some(public_key));
+
+    let payload = [1u8; 32];
+    let check = |signature: AccountSignature, function: &Symbol| {
+        let context = Context::Contract(ContractContext {
+            contract: counter.clone(),
+            fn_name: function.clone(),
+            args: vec![&env, 1i128.into_val(&env)],
+        });
+        env.try_invoke_contract_check_auth::<AAError>(
+            &contract_id,
+            &BytesN::from_array(&env, &payload),
+            signature.into_val(&env),
+            &vec![&env, context],
+        )
+    };
+
+    let assertion = passkey_sign(&env, &passkey, &payload);
+    assert!(check(AccountSignature::Passkey(assertion.clone()), &add).is_ok());
+
+    // Scoped like any session key
+    let fail = Symbol::new(&env, "fail");
+    assert_eq!(check(AccountSignature::Passkey(assertion.clone()), &fail).err(), Some(Ok(AAError::OutOfScope)));
+
+    // The clientDataJSON challenge must be this payload
+    let other = passkey_sign(&env, &passkey, &[2u8; 32]);
+    assert_eq!(check(AccountSignature::Passkey(other), &add).err(), Some(Ok(AAError::InvalidWebAuthn)));
+
+    // Assertions without user presence are rejected
+    let mut absent = assertion.clone();
+    absent.authenticator_data.set(32, 0);
+    assert_eq!(check(AccountSignature::Passkey(absent), &add).err(), Some(Ok(AAError::InvalidWebAuthn)));
+
+    // A passkey's id cannot be used as an ed25519 session key
+    let session = SessionSignature { public_key: key_id, signature: BytesN::from_array(&env, &[0u8; 64]) };
+    assert_eq!(check(AccountSignature::SessionKey(session), &add).err(), Some(Ok(AAError::InvalidSessionKey)));
+
+    // Unregistered passkeys are rejected
+    let stranger = passkey_sign(&env, &P256SigningKey::from_slice(&[8u8; 32]).unwrap(), &payload);
+    assert_eq!(check(AccountSignature::Passkey(stranger), &add).err(), Some(Ok(AAError::InvalidSessionKey)));
+}
+
+#[test]
+fn test_multisig_passkey_signer() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, _gas_token) = setup(&env);
+    let passkey = P256SigningKey::from_slice(&[9u8; 32]).unwrap();
+    let device = Signer::Passkey(passkey_public_key(&env, &passkey));
+    let a = Address::generate(&env);
+    client.setup_multisig(&contract_id, &map![&env, (device.clone(), 1), (Signer::Address(a.clone()), 1)], &2, &Map::new(&env));
+
+    let payload = [1u8; 32];
+    let check = |proofs: Vec<SignerProof>| {
+        env.try_invoke_contract_check_auth::<AAError>(
+            &contract_id,
+            &BytesN::from_array(&env, &payload),
+            AccountSignature::MultiSig(proofs).into_val(&env),
+            &Vec::new(&env),
+        )
+    };
+
+    let assertion = SignerProof::Passkey(passkey_sign(&env, &passkey, &payload));
+    assert!(check(vec![&env, assertion.clone(), SignerProof::Address(a.clone())]).is_ok());
+    assert_eq!(check(vec![&env, assertion.clone()]).err(), Some(Ok(AAError::ThresholdNotMet)));
+    assert_eq!(check(vec![&env, assertion.clone(), assertion]).err(), Some(Ok(AAError::DuplicateSigner)));
+
+    // A valid assertion for another payload does not count
+    let stale = SignerProof::Passkey(passkey_sign(&env, &passkey, &[2u8; 32]));
+    assert_eq!(
+        check(vec![&env, stale, SignerProof::Address(a.clone())]).err(),
+        Some(Ok(AAError::InvalidWebAuthn))
+    );
+
+    // The passkey's weight counts toward signer changes, bound to the change and nonce
+    let b = Address::generate(&env);
+    let change = |change: SignerChange, nonce: u64| {
+        let args = vec![&env, contract_id.into_val(&env), change.into_val(&env), nonce.into_val(&env)];
+        vec![&env, passkey_approval(&env, &contract_id, &passkey, "signers", args), SignerProof::Address(a.clone())]
+    };
+    client.add_signer(&contract_id, &Signer::Address(b.clone()), &1, &change(SignerChange::Add(Signer::Address(b.clone()), 1), 0));
+    assert_eq!(client.get_multisig(&contract_id).unwrap().0.len(), 3);
+
+    let approvals = change(SignerChange::ChangeThreshold(2), 1);
+    client.change_threshold(&contract_id, &2, &approvals);
+    assert_eq!(client.try_change_threshold(&contract_id, &2, &approvals).err(), Some(Ok(AAError::InvalidWebAuthn)));
+
+    // ...and toward proposals it makes or approves
+    let counter = env.register(Counter, ());
+    let add = Symbol::new(&env, "add");
+    let args = vec![&env, 4i128.into_val(&env)];
+    let propose_args = (contract_id.clone(), counter.clone(), add.clone(), args.clone(), 3600u64, 0u64).into_val(&env);
+    let id = client.propose(&contract_id, &passkey_approval(&env, &contract_id, &passkey, "propose", propose_args), &counter, &add, &args, &3600);
+    assert_eq!(client.get_proposal(&id).unwrap().proposer, device);
+    assert_eq!(client.try_execute_proposal(&id).err(), Some(Ok(AAError::ThresholdNotMet)));
+
+    client.approve(&id, &SignerProof::Address(b));
+    let result: i128 = client.execute_proposal(&id).into_val(&env);
+    assert_eq!(result, 4);
+}
+
+#[test]
+fn test_execute_batch() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let user = contract_id.clone();
+    let sponsor = Address::generate(&env);
+    let counter = env.register(Counter, ());
+
+    client.set_sponsor(&user, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    let add = |amount: i128| Call {
+        contract: counter.clone(),
+        function: Symbol::new(&env, "add"),
+        args: vec![&env, amount.into_val(&env)],
+    };
+    let deadline = env.ledger().timestamp() + 60;
+
+    // One authorization over the whole batch, one nonce, gas charged once
+    let calls = vec![&env, add(2), add(3)];
+    let results = client.execute_batch(&user, &relayer, &0, &MAX_FEE, &calls, &0, &deadline, &None);
+    assert_eq!(i128::try_from_val(&env, &results.get(1).unwrap()).unwrap(), 5);
+    let approval = AuthorizedFunction::Contract((
+        client.address.clone(),
+        Symbol::new(&env, "execute_batch"),
+        (calls, MAX_FEE, 0u64, deadline).into_val(&env),
+    ));
+    assert!(env.auths().iter().any(|(addr, invocation)| *addr == user && invocation.function == approval));
+    assert_eq!(client.get_nonce(&user), 1);
+    assert_eq!(client.get_sponsor_pool(&sponsor), SponsorPool { balance: 100000 - 1000, spent: 1000 });
+    assert_eq!(TokenClient::new(&env, &gas_token.address).balance(&relayer), 1000);
+
+    // A failing call reverts the earlier calls, the nonce and the gas charge
+    let fail = Call { contract: counter.clone(), function: Symbol::new(&env, "fail"), args: Vec::new(&env) };
+    let result = client.try_execute_batch(&user, &relayer, &0, &MAX_FEE, &vec![&env, add(10), fail], &1, &deadline, &None);
+    assert!(result.is_err());
+    assert_eq!(client.get_nonce(&user), 1);
+    assert_eq!(client.get_sponsor_pool(&sponsor).balance, 100000 - 1000);
+    let results = client.execute_batch(&user, &relayer, &0, &MAX_FEE, &vec![&env, add(1)], &1, &deadline, &None);
+    assert_eq!(i128::try_from_val(&env, &results.get(0).unwrap()).unwrap(), 6);
+
+    assert!(client.try_execute_batch(&user, &relayer, &0, &MAX_FEE, &Vec::new(&env), &2, &deadline, &None).is_err());
+}
+
+#[test]
+fn test_execute_batch_session_key() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let user = contract_id.clone();
+    let sponsor = Address::generate(&env);
+    let counter = env.register(Counter, ());
+    let add = Symbol::new(&env, "add");
+
+    client.set_sponsor(&user, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    let session_key = SigningKey::from_bytes(&[3u8; 32]);
+    let public_key = BytesN::from_array(&env, &session_key.verifying_key().to_bytes());
+    let scope = SessionScope {
+        contract: counter.clone(),
+        function: add.clone(),
+        arg_limits: vec![&env, ArgLimit { index: 0, max: 10 }],
+    };
+    client.add_session_key(&user, &public_key, &3600, &vec![&env, scope]);
+
+    let deadline = env.ledger().timestamp() + 60;
+    let sign_batch = |calls: &Vec<Call>, nonce: u64| {
+        let payload = BatchPayload {
+            user: user.clone(),
+            verifier: contract_id.clone(),
+            calls_hash: env.crypto().sha256(&calls.clone().to_xdr(&env)).to_bytes(),
+            max_fee: MAX_FEE,
+            nonce,
+            expires_at: deadline,
+        };
+        let xdr = payload.to_xdr(&env);
+        let mut message = [0u8; 512];
+        xdr.copy_into_slice(&mut message[..xdr.len() as usize]);
+        let signature = session_key.sign(&message[..xdr.len() as usize]).to_bytes();
+        Some(SessionAuth { public_key: public_key.clone(), signature: BytesN::from_array(&env, &signature) })
+    };
+    let call = |amount: i128| Call { contract: counter.clone(), function: add.clone(), args: vec![&env, amount.into_val(&env)] };
+
+    // Every call in the batch is scope-checked
+    let calls = vec![&env, call(5), call(50)];
+    assert_eq!(
+        client.try_execute_batch(&user, &relayer, &0, &MAX_FEE, &calls, &0, &deadline, &sign_batch(&calls, 0)).err(),
+        Some(Ok(AAError::OutOfScope))
+    );
+
+    let calls = vec![&env, call(5), call(6)];
+    let results = client.execute_batch(&user, &relayer, &0, &MAX_FEE, &calls, &0, &deadline, &sign_batch(&calls, 0));
+    assert_eq!(results.len(), 2);
+    assert_eq!(client.get_nonce(&user), 1);
+}
+
+#[test]
+fn test_fee_schedule() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let user = contract_id.clone();
+    let sponsor = Address::generate(&env);
+    let counter = env.register(Counter, ());
+    let add = Symbol::new(&env, "add");
+
+    client.set_sponsor(&user, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    client.set_fee_schedule(&FeeSchedule {
+        base_fee: 100,
+        call_fee: 50,
+        per_byte_fee: 2,
+        target_multipliers: map![&env, (counter.clone(), 20_000)],
+        max_network_fee: 500,
+    });
+
+    let args = vec![&env, 2i128.into_val(&env)];
+    let deadline = env.ledger().timestamp() + 60;
+
+    // The relayer cannot claim more than the cap
+    assert_eq!(
+        client.try_execute_metatx(&user, &relayer, &501, &MAX_FEE, &counter, &add, &args, &0, &deadline, &None).err(),
+        Some(Ok(AAError::FeeAboveCap))
+    );
+
+    // Nor charge the sponsor more than the user's signed max_fee
+    assert_eq!(
+        client.try_execute_metatx(&user, &relayer, &300, &400, &counter, &add, &args, &0, &deadline, &None).err(),
+        Some(Ok(AAError::SlippageExceeded))
+    );
+
+    client.execute_metatx(&user, &relayer, &300, &MAX_FEE, &counter, &add, &args, &0, &deadline, &None);
+
+    // Counter calls cost double: (50 + 2 * arg bytes) * 2
+    let execution_fee = (50 + 2 * args.clone().to_xdr(&env).len() as i128) * 2;
+    let receipt = FeeReceipt {
+        sponsor: sponsor.clone(),
+        relayer: relayer.clone(),
+        network_fee: 300,
+        base_fee: 100,
+        execution_fee,
+        total: 300 + 100 + execution_fee,
+    };
+    assert_eq!(
+        env.events().all().filter_by_contract(&client.address),
+        vec![
+            &env,
+            (
+                client.address.clone(),
+                (symbol_short!("metatx"), user.clone()).into_val(&env),
+                (counter.clone(), add.clone(), sponsor.clone(), receipt.total, env.ledger().timestamp()).into_val(&env),
+            ),
+            (
+                client.address.clone(),
+                (symbol_short!("receipt"), user.clone()).into_val(&env),
+                receipt.clone().into_val(&env),
+            ),
+        ]
+    );
+    assert_eq!(client.get_sponsor_pool(&sponsor).spent, receipt.total);
+    assert_eq!(TokenClient::new(&env, &gas_token.address).balance(&relayer), receipt.total);
+
+    // Only the owner may change the schedule
+    env.set_auths(&[]);
+    assert!(client.try_set_fee_schedule(&client.get_fee_schedule()).is_err());
+}
+
+#[test]
+fn test_paymaster() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let user = contract_id.clone();
+    let sponsor = Address::generate(&env);
+    let counter = env.register(Counter, ());
+    let add = Symbol::new(&env, "add");
+
+    client.set_sponsor(&user, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+
+    let usdc = env.register_stellar_asset_contract_v2(Address::generate(&env)).address();
+    StellarAssetClient::new(&env, &usdc).mint(&user, &10000);
+    let usdc_client = TokenClient::new(&env, &usdc);
+
+    // Only tokens with an owner-set rate are accepted
+    assert_eq!(
+        client.try_set_fee_payment(&user, &usdc).err(),
+        Some(Ok(AAError::UnsupportedFeeToken))
+    );
+
+    // The gas token cannot repay sponsors out of their own pooled deposits
+    assert_eq!(
+        client.try_set_fee_token_rate(&gas_token.address, &10_000_000).err(),
+        Some(Ok(AAError::GasTokenNotAllowed))
+    );
+
+    // 0.5 USDC per gas token unit
+    client.set_fee_token_rate(&usdc, &5_000_000);
+    assert_eq!(client.quote_fee(&usdc, &1000), 500);
+    client.set_fee_payment(&user, &usdc);
+    assert!(client.get_features(&user).paymaster);
+
+    let args = vec![&env, 1i128.into_val(&env)];
+    let deadline = env.ledger().timestamp() + 60;
+    client.execute_metatx(&user, &relayer, &0, &600, &counter, &add, &args, &0, &deadline, &None);
+
+    // The signed max_fee is exactly what the user authorized
+    let (_, invocation) = env.auths().into_iter().find(|(address, _)| *address == user).unwrap();
+    assert_eq!(
+        invocation.function,
+        AuthorizedFunction::Contract((
+            contract_id.clone(),
+            Symbol::new(&env, "execute_metatx"),
+            (counter.clone(), add.clone(), args.clone(), 600i128, 0u64, deadline).into_val(&env),
+        ))
+    );
+
+    // The sponsor covered the gas and was repaid in USDC
+    assert_eq!(client.get_sponsor_pool(&sponsor).spent, 1000);
+    assert_eq!(usdc_client.balance(&sponsor), 500);
+    assert_eq!(usdc_client.balance(&user), 9500);
+
+    // A fee beyond the user's signed bound, e.g. after a worse rate, aborts the whole meta-tx
+    assert_eq!(
+        client.try_execute_metatx(&user, &relayer, &0, &499, &counter, &add, &args, &1, &deadline, &None).err(),
+        Some(Ok(AAError::SlippageExceeded))
+    );
+    client.set_fee_token_rate(&usdc, &7_000_000);
+    assert_eq!(
+        client.try_execute_metatx(&user, &relayer, &0, &600, &counter, &add, &args, &1, &deadline, &None).err(),
+        Some(Ok(AAError::SlippageExceeded))
+    );
+    assert_eq!(client.get_nonce(&user), 1);
+    assert_eq!(client.get_sponsor_pool(&sponsor).spent, 1000);
+    assert_eq!(usdc_client.balance(&user), 9500);
+
+    // Turning the paymaster off returns to plain sponsorship
+    client.set_feature(&user, &Feature::Paymaster, &false);
+    client.execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &args, &1, &deadline, &None);
+    assert_eq!(usdc_client.balance(&user), 9500);
+}
+
+#[test]
+fn test_emergency_pause() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let user = contract_id.clone();
+    let sponsor = Address::generate(&env);
+    let guardian = Address::generate(&env);
+    let counter = env.register(Counter, ());
+
+    client.set_sponsor(&user, &sponsor);
+    gas_token.mint(&sponsor, &100000);
+    client.fund_gas_pool(&sponsor, &100000);
+    client.add_session_key(&user, &BytesN::from_array(&env, &[1u8; 32]), &3600, &Vec::new(&env));
+    let session_key = SigningKey::from_bytes(&[3u8; 32]);
+    let public_key = BytesN::from_array(&env, &session_key.verifying_key().to_bytes());
+    client.add_session_key(&user, &public_key, &3600, &Vec::new(&env));
+    let passkey = P256SigningKey::from_slice(&[9u8; 32]).unwrap();
+    client.add_passkey_session_key(&user, &passkey_public_key(&env, &passkey), &3600, &Vec::new(&env));
+
+    let payload = [1u8; 32];
+    let check = |signature: AccountSignature| {
+        env.try_invoke_contract_check_auth::<AAError>(
+            &contract_id,
+            &BytesN::from_array(&env, &payload),
+            signature.into_val(&env),
+            &Vec::new(&env),
+        )
+    };
+    let session = AccountSignature::SessionKey(SessionSignature {
+        public_key,
+        signature: BytesN::from_array(&env, &session_key.sign(&payload).to_bytes()),
+    });
+    let passkey_signature = AccountSignature::Passkey(passkey_sign(&env, &passkey, &payload));
+    assert!(check(session.clone()).is_ok());
+    assert!(check(passkey_signature.clone()).is_ok());
+
+    // Only the owner or the guardian can pause
+    assert_eq!(client.try_pause(&guardian).err(), Some(Ok(AAError::NotAuthorized)));
+    client.set_guardian(&Some(guardian.clone()));
+    client.pause(&guardian);
+    assert!(client.is_paused());
+
+    let add = Symbol::new(&env, "add");
+    let args = vec![&env, 1i128.into_val(&env)];
+    let deadline = env.ledger().timestamp() + 60;
+    assert_eq!(
+        client.try_execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &args, &0, &deadline, &None).err(),
+        Some(Ok(AAError::Paused))
+    );
+    let calls = vec![&env, Call { contract: counter.clone(), function: add.clone(), args: args.clone() }];
+    assert_eq!(
+        client.try_execute_batch(&user, &relayer, &0, &MAX_FEE, &calls, &0, &deadline, &None).err(),
+        Some(Ok(AAError::Paused))
+    );
+    assert_eq!(
+        client.try_set_sponsor_policy(&user, &SponsorPolicy {
+            max_spend: None,
+            tx_quota: None,
+            period: 0,
+            allowed_targets: Vec::new(&env),
+            expires_at: None,
+        }).err(),
+        Some(Ok(AAError::Paused))
+    );
+    assert_eq!(
+        client.try_add_session_key(&user, &BytesN::from_array(&env, &[2u8; 32]), &3600, &Vec::new(&env)).err(),
+        Some(Ok(AAError::Paused))
+    );
+    assert_eq!(client.try_set_feature(&user, &Feature::SessionKeys, &false).err(), Some(Ok(AAError::Paused)));
+
+    // Session and passkey signatures no longer authorize the account; the owner still does
+    assert_eq!(check(session.clone()).err(), Some(Ok(AAError::Paused)));
+    assert_eq!(check(passkey_signature.clone()).err(), Some(Ok(AAError::Paused)));
+    assert!(check(AccountSignature::Owner).is_ok());
+
+    // Revocations and withdrawals stay available
+    client.revoke_session_key(&user, &BytesN::from_array(&env, &[1u8; 32]));
+    client.withdraw_gas_pool(&sponsor, &1000);
+
+    // Only the owner lifts the pause
+    client.unpause();
+    assert_ne!(env.auths()[0].0, guardian);
+    assert!(!client.is_paused());
+    assert!(check(session).is_ok());
+    assert!(check(passkey_signature).is_ok());
+    let result = client.execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &args, &0, &deadline, &None);
+    assert_eq!(i128::try_from_val(&env, &result).unwrap(), 1);
+}
+
+#[test]
+fn test_typed_errors() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client, gas_token) = setup(&env);
+    let relayer = Address::generate(&env);
+    let user = contract_id.clone();
+    let sponsor = Address::generate(&env);
+    let counter = env.register(Counter, ());
+    let add = Symbol::new(&env, "add");
+    let args = vec![&env, 1i128.into_val(&env)];
+    let deadline = env.ledger().timestamp() + 60;
+
+    assert_eq!(
+        client.try_initialize(&Address::generate(&env), &gas_token.address).err(),
+        Some(Ok(AAError::AlreadyInitialized))
+    );
+    assert_eq!(
+        client.try_execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &args, &0, &deadline, &None).err(),
+        Some(Ok(AAError::SponsorshipNotEnabled))
+    );
+    assert_eq!(client.try_revoke_sponsorship(&user).err(), Some(Ok(AAError::NoSponsor)));
+    assert_eq!(client.try_fund_gas_pool(&sponsor, &0).err(), Some(Ok(AAError::InvalidAmount)));
+
+    client.set_sponsor(&user, &sponsor);
+    assert_eq!(
+        client.try_execute_metatx(&user, &relayer, &0, &MAX_FEE, &counter, &add, &args, &0, &deadline, &None).err(),
+        Some(Ok(AAError::InsufficientGasPool))
+    );
+    assert_eq!(
+        client.try_execute_batch(&user, &relayer, &0, &MAX_FEE, &Vec::new(&env), &0, &deadline, &None).err(),
+        Some(Ok(AAError::EmptyBatch))
+    );
+    assert_eq!(client.try_cancel_proposal(&7, &SignerProof::Address(sponsor.clone())).err(), Some(Ok(AAError::ProposalNotFound)));
+    assert_eq!(
+        client.try_add_signer(&user, &Signer::Address(sponsor.clone()), &1, &address_proofs(&env, &[&sponsor])).err(),
+        Some(Ok(AAError::MultisigNotConfigured))
+    );
+    assert_eq!(
+        client.try_propose(&user, &SignerProof::Address(sponsor.clone()), &counter, &add, &args, &3600).err(),
+        Some(Ok(AAError::MultisigNotConfigured))
+    );
+    assert_eq!(
+        client.try_setup_multisig(&user, &map![&env, (Signer::Address(sponsor.clone()), 1)], &2, &Map::new(&env)).err(),
+        Some(Ok(AAError::InvalidThreshold))
+    );
+}
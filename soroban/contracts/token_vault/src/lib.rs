@@ -1,13 +1,56 @@
 #![no_std]
-use soroban_sdk::{contract, contractimpl, contracttype, symbol_short, Address, Bytes, BytesN, Env, String};
+// store_token takes 8 arguments; the `TokenVaultArgs`/client helpers #[contractimpl] generates
+// for it are emitted outside the impl without the function's attributes, so a function-level allow
+// does not reach them
+#![allow(clippy::too_many_arguments)]
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, panic_with_error, symbol_short, xdr::ToXdr, Address, Bytes,
+    BytesN, Env, IntoVal, Map, String, Topics, Val, Vec,
+};
+
+/// Default delay before a queued governance operation can execute (48 hours)
+const DEFAULT_TIMELOCK_DELAY: u64 = 172800;
+/// Upper bound on the configurable timelock delay (30 days)
+const MAX_TIMELOCK_DELAY: u64 = 2592000;
+/// Lower bound on the configurable timelock delay (1 hour)
+const MIN_TIMELOCK_DELAY: u64 = 3600;
+/// Window after the ETA in which a queued operation can still execute (14 days)
+const GRACE_PERIOD: u64 = 1209600;
+/// Maximum number of tokens a single user can hold
+const MAX_TOKENS_PER_USER: u32 = 16;
+/// Smallest valid encrypted payload: 12-byte AES-GCM nonce plus 16-byte tag
+const MIN_PAYLOAD_SIZE: u32 = 28;
+/// Default upper bound on the encrypted payload size
+const DEFAULT_MAX_PAYLOAD_SIZE: u32 = 1024;
+/// Accepted card networks; keeps the per-network stats map bounded
+const CARD_NETWORKS: [&str; 4] = ["visa", "mastercard", "rupay", "amex"];
 
 /// Storage keys
 #[contracttype]
 pub enum DataKey {
-    TokenData(Address),     // User address -> encrypted token data
+    TokenData(Address, BytesN<32>), // (User address, token hash) -> encrypted token data
+    UserTokens(Address),    // User address -> token hashes held by the user
     Permissions(Address),   // User address -> access permissions
     Owner,                  // Contract owner
     TokenCount,             // Total tokens stored
+    TimelockDelay,          // Delay applied to queued governance operations
+    NextOperationId,        // Next governance operation id
+    Operation(u64),         // Operation id -> queued governance operation
+    Stats,                  // Live token statistics
+    Issuer(BytesN<32>),     // Issuer ed25519 public key -> registered flag
+    MaxPayloadSize,         // Upper bound on encrypted payload length
+}
+
+/// Token input validation errors
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum VaultError {
+    InvalidLastFourDigits = 1,  // Not exactly four ASCII digits
+    PayloadTooShort = 2,        // Shorter than nonce plus tag
+    PayloadTooLarge = 3,        // Longer than the configured maximum
+    ZeroTokenHash = 4,          // token_hash is all zeros
+    UnsupportedCardNetwork = 5, // Not one of CARD_NETWORKS
 }
 
 /// Token metadata structure
@@ -18,10 +61,60 @@ pub struct TokenMetadata {
     pub encrypted_payload: Bytes,  // Ring AES-GCM encrypted card data
     pub token_hash: BytesN<32>,    // SHA-256 hash for indexing
     pub last_4_digits: String,     // Last 4 digits for display
-    pub card_network: String,      // visa, mastercard, rupay
-    pub status: String,            // active, revoked, expired
+    pub card_network: String,      // visa, mastercard, rupay, amex
+    pub status: String,            // active, suspended, revoked, expired
     pub created_at: u64,           // Unix timestamp
     pub expires_at: u64,           // Unix timestamp
+    pub issuer: Option<BytesN<32>>, // Issuer whose attestation was checked on store
+}
+
+/// Card token to be stored
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NewToken {
+    pub encrypted_payload: Bytes,
+    pub token_hash: BytesN<32>,
+    pub last_4_digits: String,
+    pub card_network: String,
+    pub expires_at: u64,
+    pub attestation: TokenAttestation,
+}
+
+/// Optional issuer attestation attached to a new token
+/// (an enum rather than `Option<Attestation>`: the SDK cannot convert an optional custom struct field to XDR)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TokenAttestation {
+    Unattested,
+    Issuer(Attestation),
+}
+
+/// Issuer signature over the token's display fields
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Attestation {
+    pub issuer: BytesN<32>,        // Registered issuer ed25519 public key
+    pub signature: BytesN<64>,     // ed25519 signature over the XDR of `AttestationPayload`
+}
+
+/// Fields covered by an issuer attestation (bound to the user and vault so it cannot be replayed)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AttestationPayload {
+    pub user: Address,
+    pub vault: Address,
+    pub token_hash: BytesN<32>,
+    pub last_4_digits: String,
+    pub card_network: String,
+    pub expires_at: u64,
+}
+
+/// Per-token outcome of a batch revocation
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RevokeResult {
+    pub token_hash: BytesN<32>,
+    pub revoked: bool,              // false if the token was already revoked
 }
 
 /// Access permission levels
@@ -33,6 +126,40 @@ pub enum Permission {
     Revoked,    // No access
 }
 
+/// Live token counts, updated on every status transition
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TokenStats {
+    pub active: u32,
+    pub suspended: u32,             // Active tokens whose permission was revoked by the owner
+    pub revoked: u32,
+    pub expired: u32,
+    pub purged: u32,                // Tokens deleted from storage (cumulative)
+    pub by_network: Map<String, u32>, // card_network -> tokens currently stored
+}
+
+/// Privileged actions that must go through the timelock
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GovernanceAction {
+    UpdatePermissions(Address, Permission),
+    Unpause,
+    Upgrade(BytesN<32>),        // New contract WASM hash
+    SetTimelockDelay(u64),
+    RegisterIssuer(BytesN<32>),   // Issuer or BIN sponsor ed25519 public key
+    SetMaxPayloadSize(u32),
+}
+
+/// Governance operation waiting in the timelock queue
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QueuedOperation {
+    pub id: u64,
+    pub action: GovernanceAction,
+    pub queued_at: u64,            // Unix timestamp
+    pub eta: u64,                  // Earliest execution time
+}
+
 #[contract]
 pub struct TokenVault;
 
@@ -47,11 +174,14 @@ impl TokenVault {
         owner.require_auth();
         env.storage().instance().set(&DataKey::Owner, &owner);
         env.storage().instance().set(&DataKey::TokenCount, &0u32);
+        env.storage().instance().set(&DataKey::TimelockDelay, &DEFAULT_TIMELOCK_DELAY);
+        env.storage().instance().set(&DataKey::MaxPayloadSize, &DEFAULT_MAX_PAYLOAD_SIZE);
     }
 
     /// Store encrypted card token
     /// encrypted_payload: AES-GCM encrypted card data (ring library used client-side)
     /// token_hash: SHA-256 hash of the original card data for indexing
+    /// attestation: optional issuer signature; the issuer is recorded on the token when present
     pub fn store_token(
         env: Env,
         user: Address,
@@ -60,53 +190,58 @@ impl TokenVault {
         last_4_digits: String,
         card_network: String,
         expires_at: u64,
+        attestation: Option<Attestation>,
     ) -> TokenMetadata {
         user.require_auth();
 
-        // Check if token already exists
-        if env.storage().persistent().has(&DataKey::TokenData(user.clone())) {
-            panic!("Token already exists for this user");
-        }
-
-        let current_time = env.ledger().timestamp();
-        
-        if expires_at <= current_time {
-            panic!("Expiration date must be in the future");
-        }
-
-        let metadata = TokenMetadata {
-            user: user.clone(),
-            encrypted_payload: encrypted_payload.clone(),
-            token_hash: token_hash.clone(),
-            last_4_digits: last_4_digits.clone(),
-            card_network: card_network.clone(),
-            status: String::from_str(&env, "active"),
-            created_at: current_time,
+        let token = NewToken {
+            encrypted_payload,
+            token_hash,
+            last_4_digits,
+            card_network,
             expires_at,
+            attestation: match attestation {
+                Some(attestation) => TokenAttestation::Issuer(attestation),
+                None => TokenAttestation::Unattested,
+            },
         };
 
-        // Store token data (persistent storage for long-term retention)
-        env.storage().persistent().set(&DataKey::TokenData(user.clone()), &metadata);
-        
-        // Set permission
-        env.storage().persistent().set(&DataKey::Permissions(user.clone()), &Permission::Owner);
-
-        // Increment token count
-        let mut count: u32 = env.storage().instance().get(&DataKey::TokenCount).unwrap_or(0);
-        count += 1;
-        env.storage().instance().set(&DataKey::TokenCount, &count);
+        let mut token_hashes = Self::list_tokens(env.clone(), user.clone());
+        Self::validate_new_token(&env, &user, &token_hashes, &token);
 
-        // Emit event
-        env.events().publish(
-            (symbol_short!("store"), user.clone()),
-            (token_hash, card_network, current_time)
-        );
+        let metadata = Self::write_token(&env, &user, token);
+        token_hashes.push_back(metadata.token_hash.clone());
+        env.storage().persistent().set(&DataKey::UserTokens(user), &token_hashes);
 
         metadata
     }
 
+    /// Store several card tokens atomically (all-or-nothing)
+    pub fn store_tokens(env: Env, user: Address, tokens: Vec<NewToken>) -> Vec<TokenMetadata> {
+        user.require_auth();
+
+        if tokens.is_empty() {
+            panic!("No tokens provided");
+        }
+
+        // Validate the whole batch before writing anything
+        let mut token_hashes = Self::list_tokens(env.clone(), user.clone());
+        for token in tokens.iter() {
+            Self::validate_new_token(&env, &user, &token_hashes, &token);
+            token_hashes.push_back(token.token_hash.clone());
+        }
+
+        let mut stored = Vec::new(&env);
+        for token in tokens.iter() {
+            stored.push_back(Self::write_token(&env, &user, token));
+        }
+        env.storage().persistent().set(&DataKey::UserTokens(user), &token_hashes);
+
+        stored
+    }
+
     /// Retrieve encrypted token (only owner can access)
-    pub fn retrieve_token(env: Env, user: Address) -> Option<TokenMetadata> {
+    pub fn retrieve_token(env: Env, user: Address, token_hash: BytesN<32>) -> Option<TokenMetadata> {
         user.require_auth();
 
         // Check permissions
@@ -114,21 +249,24 @@ impl TokenVault {
         
         match permission {
             Some(Permission::Owner) | Some(Permission::Read) => {
-                let metadata: Option<TokenMetadata> = env.storage().persistent().get(&DataKey::TokenData(user.clone()));
+                let key = DataKey::TokenData(user.clone(), token_hash);
+                let metadata: Option<TokenMetadata> = env.storage().persistent().get(&key);
                 
                 if let Some(ref token) = metadata {
+                    if token.status == String::from_str(&env, "revoked") {
+                        return None;
+                    }
+
                     // Check if token is expired
                     let current_time = env.ledger().timestamp();
                     if current_time > token.expires_at {
-                        // Auto-revoke expired tokens
                         let mut expired_token = token.clone();
-                        expired_token.status = String::from_str(&env, "expired");
-                        env.storage().persistent().set(&DataKey::TokenData(user.clone()), &expired_token);
+                        Self::expire(&env, &user, &mut expired_token);
                         return Some(expired_token);
                     }
                     
                     // Emit access event
-                    env.events().publish(
+                    publish(&env,
                         (symbol_short!("access"), user),
                         current_time
                     );
@@ -141,84 +279,542 @@ impl TokenVault {
     }
 
     /// Revoke token (user can revoke their own token)
-    pub fn revoke_token(env: Env, user: Address) -> bool {
+    pub fn revoke_token(env: Env, user: Address, token_hash: BytesN<32>) -> bool {
         user.require_auth();
 
-        let metadata: Option<TokenMetadata> = env.storage().persistent().get(&DataKey::TokenData(user.clone()));
-        
-        if let Some(mut token) = metadata {
-            token.status = String::from_str(&env, "revoked");
-            env.storage().persistent().set(&DataKey::TokenData(user.clone()), &token);
-            
-            // Update permission
-            env.storage().persistent().set(&DataKey::Permissions(user.clone()), &Permission::Revoked);
-            
-            // Emit revocation event
-            env.events().publish(
-                (symbol_short!("revoke"), user),
-                env.ledger().timestamp()
-            );
-            
-            true
+        if !env.storage().persistent().has(&DataKey::TokenData(user.clone(), token_hash.clone())) {
+            return false;
+        }
+
+        Self::revoke(&env, &user, &token_hash)
+    }
+
+    /// Record that a token is past its expiry (anyone can call, so stats never wait on a read)
+    /// Returns false if the token is unknown, not yet expired, revoked or already expired
+    pub fn expire_token(env: Env, user: Address, token_hash: BytesN<32>) -> bool {
+        let token: Option<TokenMetadata> = env.storage().persistent().get(&DataKey::TokenData(user.clone(), token_hash));
+        match token {
+            Some(mut token) => Self::expire(&env, &user, &mut token),
+            None => false,
+        }
+    }
+
+    /// Revoke every token held by the user in one call
+    pub fn revoke_all(env: Env, user: Address) -> Vec<RevokeResult> {
+        user.require_auth();
+
+        let mut results = Vec::new(&env);
+        for token_hash in Self::list_tokens(env.clone(), user.clone()).iter() {
+            let revoked = Self::revoke(&env, &user, &token_hash);
+            results.push_back(RevokeResult { token_hash, revoked });
+        }
+
+        results
+    }
+
+    /// Purge a revoked or expired token from storage (user can purge their own token)
+    pub fn purge_token(env: Env, user: Address, token_hash: BytesN<32>) {
+        user.require_auth();
+
+        let token: TokenMetadata = env.storage().persistent()
+            .get(&DataKey::TokenData(user.clone(), token_hash.clone()))
+            .expect("Token not found");
+
+        let is_expired = env.ledger().timestamp() > token.expires_at;
+        if token.status != String::from_str(&env, "revoked")
+            && token.status != String::from_str(&env, "expired")
+            && !is_expired
+        {
+            panic!("Only revoked or expired tokens can be purged");
+        }
+
+        env.storage().persistent().remove(&DataKey::TokenData(user.clone(), token_hash.clone()));
+
+        let mut token_hashes = Self::list_tokens(env.clone(), user.clone());
+        if let Some(index) = token_hashes.first_index_of(&token_hash) {
+            token_hashes.remove(index);
+        }
+        if token_hashes.is_empty() {
+            env.storage().persistent().remove(&DataKey::UserTokens(user.clone()));
         } else {
-            false
+            env.storage().persistent().set(&DataKey::UserTokens(user.clone()), &token_hashes);
+        }
+
+        Self::record_transition(&env, Some(&token.status), None, &token.card_network);
+
+        publish(&env,
+            (symbol_short!("purge"), user),
+            (token_hash, env.ledger().timestamp())
+        );
+    }
+
+    /// List token hashes held by a user
+    pub fn list_tokens(env: Env, user: Address) -> Vec<BytesN<32>> {
+        env.storage().persistent()
+            .get(&DataKey::UserTokens(user))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Check whether a token is active and attested by an issuer that is still registered
+    pub fn is_token_verified(env: Env, user: Address, token_hash: BytesN<32>) -> bool {
+        let metadata: Option<TokenMetadata> = env.storage().persistent().get(&DataKey::TokenData(user, token_hash));
+
+        match metadata {
+            Some(token) => {
+                token.issuer.is_some_and(|issuer| env.storage().persistent().has(&DataKey::Issuer(issuer)))
+                    && token.status == String::from_str(&env, "active")
+                    && env.ledger().timestamp() <= token.expires_at
+            },
+            None => false,
         }
     }
 
-    /// Update token permissions (owner only)
-    pub fn update_permissions(env: Env, user: Address, permission: Permission) {
+    /// Queue a permission update (owner only, timelocked)
+    pub fn update_permissions(env: Env, user: Address, permission: Permission) -> u64 {
+        Self::schedule(&env, GovernanceAction::UpdatePermissions(user, permission))
+    }
+
+    /// Queue a contract upgrade (owner only, timelocked)
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> u64 {
+        Self::schedule(&env, GovernanceAction::Upgrade(new_wasm_hash))
+    }
+
+    /// Queue a change of the timelock delay (owner only, timelocked)
+    pub fn set_timelock_delay(env: Env, delay: u64) -> u64 {
+        if delay > MAX_TIMELOCK_DELAY {
+            panic!("Timelock delay too long");
+        }
+        if delay < MIN_TIMELOCK_DELAY {
+            panic!("Timelock delay too short");
+        }
+
+        Self::schedule(&env, GovernanceAction::SetTimelockDelay(delay))
+    }
+
+    /// Queue registration of an attestation issuer (owner only, timelocked)
+    pub fn register_issuer(env: Env, issuer: BytesN<32>) -> u64 {
+        Self::schedule(&env, GovernanceAction::RegisterIssuer(issuer))
+    }
+
+    /// Remove an attestation issuer (owner only) - immediate, for compromised keys;
+    /// tokens it attested stop counting as verified
+    pub fn remove_issuer(env: Env, issuer: BytesN<32>) {
         let owner: Address = env.storage().instance().get(&DataKey::Owner).unwrap();
         owner.require_auth();
-        
-        env.storage().persistent().set(&DataKey::Permissions(user.clone()), &permission);
-        
-        env.events().publish(
-            (symbol_short!("perm"), user),
+
+        env.storage().persistent().remove(&DataKey::Issuer(issuer.clone()));
+
+        publish(&env,
+            (symbol_short!("rm_issuer"), owner),
+            issuer
+        );
+    }
+
+    /// Check whether an issuer key is registered
+    pub fn is_issuer(env: Env, issuer: BytesN<32>) -> bool {
+        env.storage().persistent().has(&DataKey::Issuer(issuer))
+    }
+
+    /// Queue a change of the maximum encrypted payload size (owner only, timelocked)
+    pub fn set_max_payload_size(env: Env, max_size: u32) -> u64 {
+        if max_size < MIN_PAYLOAD_SIZE {
+            panic!("Invalid max payload size");
+        }
+
+        Self::schedule(&env, GovernanceAction::SetMaxPayloadSize(max_size))
+    }
+
+    /// Get the maximum encrypted payload size in bytes
+    pub fn get_max_payload_size(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::MaxPayloadSize).unwrap_or(DEFAULT_MAX_PAYLOAD_SIZE)
+    }
+
+    /// Execute a queued operation once its ETA has passed and before its grace period ends (owner only)
+    pub fn execute_operation(env: Env, op_id: u64) {
+        let owner: Address = env.storage().instance().get(&DataKey::Owner).unwrap();
+        owner.require_auth();
+
+        let op: QueuedOperation = env.storage().persistent()
+            .get(&DataKey::Operation(op_id))
+            .expect("Operation not found");
+
+        if env.ledger().timestamp() < op.eta {
+            panic!("Timelock not expired");
+        }
+        if env.ledger().timestamp() > op.eta + GRACE_PERIOD {
+            panic!("Operation expired");
+        }
+
+        env.storage().persistent().remove(&DataKey::Operation(op_id));
+
+        match op.action {
+            GovernanceAction::UpdatePermissions(user, permission) => {
+                env.storage().persistent().set(&DataKey::Permissions(user.clone()), &permission);
+
+                // Owner revocation suspends active tokens; restoring access reactivates them
+                let active = String::from_str(&env, "active");
+                let suspended = String::from_str(&env, "suspended");
+                for token_hash in Self::list_tokens(env.clone(), user.clone()).iter() {
+                    let key = DataKey::TokenData(user.clone(), token_hash);
+                    let mut token: TokenMetadata = env.storage().persistent().get(&key).unwrap();
+                    let new_status = match permission {
+                        Permission::Revoked if token.status == active => Some(suspended.clone()),
+                        Permission::Owner | Permission::Read if token.status == suspended => Some(active.clone()),
+                        _ => None,
+                    };
+
+                    if let Some(status) = new_status {
+                        let previous_status = token.status.clone();
+                        token.status = status;
+                        env.storage().persistent().set(&key, &token);
+                        Self::record_transition(&env, Some(&previous_status), Some(&token.status), &token.card_network);
+                    }
+                }
+
+                publish(&env,
+                    (symbol_short!("perm"), user),
+                    env.ledger().timestamp()
+                );
+            },
+            GovernanceAction::Unpause => {
+                env.storage().instance().set(&symbol_short!("paused"), &false);
+
+                publish(&env,
+                    (symbol_short!("unpause"), owner),
+                    env.ledger().timestamp()
+                );
+            },
+            GovernanceAction::Upgrade(new_wasm_hash) => {
+                env.deployer().update_current_contract_wasm(new_wasm_hash);
+            },
+            GovernanceAction::SetTimelockDelay(delay) => {
+                env.storage().instance().set(&DataKey::TimelockDelay, &delay);
+            },
+            GovernanceAction::SetMaxPayloadSize(max_size) => {
+                env.storage().instance().set(&DataKey::MaxPayloadSize, &max_size);
+            },
+            GovernanceAction::RegisterIssuer(issuer) => {
+                env.storage().persistent().set(&DataKey::Issuer(issuer.clone()), &true);
+
+                publish(&env,
+                    (symbol_short!("issuer"), owner),
+                    issuer
+                );
+            },
+        }
+
+        publish(&env,
+            (symbol_short!("execute"), op_id),
+            env.ledger().timestamp()
+        );
+    }
+
+    /// Cancel a queued operation (owner only)
+    pub fn cancel_operation(env: Env, op_id: u64) {
+        let owner: Address = env.storage().instance().get(&DataKey::Owner).unwrap();
+        owner.require_auth();
+
+        if !env.storage().persistent().has(&DataKey::Operation(op_id)) {
+            panic!("Operation not found");
+        }
+
+        env.storage().persistent().remove(&DataKey::Operation(op_id));
+
+        publish(&env,
+            (symbol_short!("cancel"), op_id),
             env.ledger().timestamp()
         );
     }
 
-    /// Get token count
+    /// Get a queued operation
+    pub fn get_operation(env: Env, op_id: u64) -> Option<QueuedOperation> {
+        env.storage().persistent().get(&DataKey::Operation(op_id))
+    }
+
+    /// Get the current timelock delay in seconds
+    pub fn get_timelock_delay(env: Env) -> u64 {
+        env.storage().instance().get(&DataKey::TimelockDelay).unwrap_or(DEFAULT_TIMELOCK_DELAY)
+    }
+
+    /// Get total number of tokens ever stored (see `get_stats` for live counts)
     pub fn get_token_count(env: Env) -> u32 {
         env.storage().instance().get(&DataKey::TokenCount).unwrap_or(0)
     }
 
+    /// Get live token counts by status and card network
+    pub fn get_stats(env: Env) -> TokenStats {
+        env.storage().instance().get(&DataKey::Stats).unwrap_or(TokenStats {
+            active: 0,
+            suspended: 0,
+            revoked: 0,
+            expired: 0,
+            purged: 0,
+            by_network: Map::new(&env),
+        })
+    }
+
     /// Get token status (public - doesn't reveal encrypted data)
-    pub fn get_token_status(env: Env, user: Address) -> Option<String> {
-        let metadata: Option<TokenMetadata> = env.storage().persistent().get(&DataKey::TokenData(user));
+    pub fn get_token_status(env: Env, user: Address, token_hash: BytesN<32>) -> Option<String> {
+        let metadata: Option<TokenMetadata> = env.storage().persistent().get(&DataKey::TokenData(user, token_hash));
         metadata.map(|m| m.status)
     }
 
-    /// Emergency pause (owner only) - for security incidents
+    /// Emergency pause (owner only) - for security incidents, not timelocked
     pub fn pause(env: Env) {
         let owner: Address = env.storage().instance().get(&DataKey::Owner).unwrap();
         owner.require_auth();
         
         env.storage().instance().set(&symbol_short!("paused"), &true);
         
-        env.events().publish(
+        publish(&env,
             (symbol_short!("pause"), owner),
             env.ledger().timestamp()
         );
     }
 
-    /// Unpause (owner only)
-    pub fn unpause(env: Env) {
-        let owner: Address = env.storage().instance().get(&DataKey::Owner).unwrap();
-        owner.require_auth();
-        
-        env.storage().instance().set(&symbol_short!("paused"), &false);
-        
-        env.events().publish(
-            (symbol_short!("unpause"), owner),
-            env.ledger().timestamp()
-        );
+    /// Queue an unpause (owner only, timelocked)
+    pub fn unpause(env: Env) -> u64 {
+        Self::schedule(&env, GovernanceAction::Unpause)
     }
 
     /// Check if contract is paused
     pub fn is_paused(env: Env) -> bool {
         env.storage().instance().get(&symbol_short!("paused")).unwrap_or(false)
     }
+
+    /// Check a new token against storage and the tokens already accepted for the user
+    fn validate_new_token(env: &Env, user: &Address, token_hashes: &Vec<BytesN<32>>, token: &NewToken) {
+        Self::validate_fields(env, token);
+
+        let permission: Option<Permission> = env.storage().persistent().get(&DataKey::Permissions(user.clone()));
+        if permission == Some(Permission::Revoked) {
+            panic!("Access revoked for this user");
+        }
+
+        // Check if token already exists
+        if token_hashes.contains(&token.token_hash) {
+            panic!("Token already exists for this user");
+        }
+
+        if token_hashes.len() >= MAX_TOKENS_PER_USER {
+            panic!("Too many tokens for this user");
+        }
+
+        if token.expires_at <= env.ledger().timestamp() {
+            panic!("Expiration date must be in the future");
+        }
+
+        if let TokenAttestation::Issuer(attestation) = &token.attestation {
+            if !env.storage().persistent().has(&DataKey::Issuer(attestation.issuer.clone())) {
+                panic!("Unknown issuer");
+            }
+
+            let payload = AttestationPayload {
+                user: user.clone(),
+                vault: env.current_contract_address(),
+                token_hash: token.token_hash.clone(),
+                last_4_digits: token.last_4_digits.clone(),
+                card_network: token.card_network.clone(),
+                expires_at: token.expires_at,
+            };
+
+            // Traps if the signature does not match
+            env.crypto().ed25519_verify(&attestation.issuer, &payload.to_xdr(env), &attestation.signature);
+        }
+    }
+
+    /// Reject malformed display fields and payloads so no PAN data leaks into public fields
+    fn validate_fields(env: &Env, token: &NewToken) {
+        let mut digits = [0u8; 4];
+        if token.last_4_digits.len() != 4 {
+            panic_with_error!(env, VaultError::InvalidLastFourDigits);
+        }
+        token.last_4_digits.copy_into_slice(&mut digits);
+        if !digits.iter().all(|d| d.is_ascii_digit()) {
+            panic_with_error!(env, VaultError::InvalidLastFourDigits);
+        }
+
+        let payload_size = token.encrypted_payload.len();
+        if payload_size < MIN_PAYLOAD_SIZE {
+            panic_with_error!(env, VaultError::PayloadTooShort);
+        }
+        if payload_size > Self::get_max_payload_size(env.clone()) {
+            panic_with_error!(env, VaultError::PayloadTooLarge);
+        }
+
+        if token.token_hash.to_array() == [0u8; 32] {
+            panic_with_error!(env, VaultError::ZeroTokenHash);
+        }
+
+        if !CARD_NETWORKS.iter().any(|network| token.card_network == String::from_str(env, network)) {
+            panic_with_error!(env, VaultError::UnsupportedCardNetwork);
+        }
+    }
+
+    /// Persist a validated token; the caller updates the user's token index
+    fn write_token(env: &Env, user: &Address, token: NewToken) -> TokenMetadata {
+        let current_time = env.ledger().timestamp();
+
+        let metadata = TokenMetadata {
+            user: user.clone(),
+            encrypted_payload: token.encrypted_payload,
+            token_hash: token.token_hash.clone(),
+            last_4_digits: token.last_4_digits,
+            card_network: token.card_network.clone(),
+            status: String::from_str(env, "active"),
+            created_at: current_time,
+            expires_at: token.expires_at,
+            issuer: match &token.attestation {
+                TokenAttestation::Issuer(attestation) => Some(attestation.issuer.clone()),
+                TokenAttestation::Unattested => None,
+            },
+        };
+
+        // Store token data (persistent storage for long-term retention)
+        env.storage().persistent().set(&DataKey::TokenData(user.clone(), token.token_hash.clone()), &metadata);
+        
+        // Set permission unless the owner already assigned one
+        if !env.storage().persistent().has(&DataKey::Permissions(user.clone())) {
+            env.storage().persistent().set(&DataKey::Permissions(user.clone()), &Permission::Owner);
+        }
+
+        // Increment token count
+        let mut count: u32 = env.storage().instance().get(&DataKey::TokenCount).unwrap_or(0);
+        count += 1;
+        env.storage().instance().set(&DataKey::TokenCount, &count);
+
+        Self::record_transition(env, None, Some(&metadata.status), &token.card_network);
+
+        // Emit event
+        publish(env,
+            (symbol_short!("store"), user.clone()),
+            (token.token_hash, token.card_network, current_time)
+        );
+
+        metadata
+    }
+
+    /// Mark a stored token revoked; returns false if it already was
+    fn revoke(env: &Env, user: &Address, token_hash: &BytesN<32>) -> bool {
+        let key = DataKey::TokenData(user.clone(), token_hash.clone());
+        let mut token: TokenMetadata = env.storage().persistent().get(&key).unwrap();
+
+        let previous_status = token.status.clone();
+        token.status = String::from_str(env, "revoked");
+        if previous_status == token.status {
+            return false;
+        }
+
+        env.storage().persistent().set(&key, &token);
+        Self::record_transition(env, Some(&previous_status), Some(&token.status), &token.card_network);
+
+        // Emit revocation event
+        publish(env,
+            (symbol_short!("revoke"), user.clone()),
+            (token_hash.clone(), env.ledger().timestamp())
+        );
+
+        true
+    }
+
+    /// Mark a token past its expiry as expired; returns false if it is not or is already settled
+    fn expire(env: &Env, user: &Address, token: &mut TokenMetadata) -> bool {
+        let expired = String::from_str(env, "expired");
+        if env.ledger().timestamp() <= token.expires_at
+            || token.status == expired
+            || token.status == String::from_str(env, "revoked")
+        {
+            return false;
+        }
+
+        let previous_status = token.status.clone();
+        token.status = expired;
+        env.storage().persistent().set(&DataKey::TokenData(user.clone(), token.token_hash.clone()), token);
+        Self::record_transition(env, Some(&previous_status), Some(&token.status), &token.card_network);
+
+        publish(env,
+            (symbol_short!("expire"), user.clone()),
+            (token.token_hash.clone(), env.ledger().timestamp())
+        );
+
+        true
+    }
+
+    /// Move one token between status counters; `None` means not stored
+    fn record_transition(env: &Env, from: Option<&String>, to: Option<&String>, card_network: &String) {
+        let mut stats = Self::get_stats(env.clone());
+
+        if let Some(status) = from {
+            let count = Self::status_counter(env, &mut stats, status);
+            *count = count.saturating_sub(1);
+        }
+
+        match to {
+            Some(status) => *Self::status_counter(env, &mut stats, status) += 1,
+            None => stats.purged += 1,
+        }
+
+        let network_count = stats.by_network.get(card_network.clone()).unwrap_or(0);
+        if from.is_none() {
+            stats.by_network.set(card_network.clone(), network_count + 1);
+        } else if to.is_none() {
+            if network_count > 1 {
+                stats.by_network.set(card_network.clone(), network_count - 1);
+            } else {
+                stats.by_network.remove(card_network.clone());
+            }
+        }
+
+        env.storage().instance().set(&DataKey::Stats, &stats);
+    }
+
+    fn status_counter<'a>(env: &Env, stats: &'a mut TokenStats, status: &String) -> &'a mut u32 {
+        if *status == String::from_str(env, "active") {
+            &mut stats.active
+        } else if *status == String::from_str(env, "suspended") {
+            &mut stats.suspended
+        } else if *status == String::from_str(env, "revoked") {
+            &mut stats.revoked
+        } else if *status == String::from_str(env, "expired") {
+            &mut stats.expired
+        } else {
+            panic!("Unknown token status");
+        }
+    }
+
+    /// Queue a governance action behind the timelock
+    fn schedule(env: &Env, action: GovernanceAction) -> u64 {
+        let owner: Address = env.storage().instance().get(&DataKey::Owner).unwrap();
+        owner.require_auth();
+
+        let op_id: u64 = env.storage().instance().get(&DataKey::NextOperationId).unwrap_or(0);
+        env.storage().instance().set(&DataKey::NextOperationId, &(op_id + 1));
+
+        let current_time = env.ledger().timestamp();
+        let delay: u64 = env.storage().instance().get(&DataKey::TimelockDelay).unwrap_or(DEFAULT_TIMELOCK_DELAY);
+
+        let op = QueuedOperation {
+            id: op_id,
+            action: action.clone(),
+            queued_at: current_time,
+            eta: current_time + delay,
+        };
+
+        env.storage().persistent().set(&DataKey::Operation(op_id), &op);
+
+        publish(env,
+            (symbol_short!("queue"), op_id),
+            (action, op.eta)
+        );
+
+        op_id
+    }
+}
+
+/// Publish a (topics, data) event; the one place the deprecated untyped event API is used
+#[allow(deprecated)]
+fn publish<T: Topics, D: IntoVal<Env, Val>>(env: &Env, topics: T, data: D) {
+    env.events().publish(topics, data);
 }
 
 #[cfg(test)]
@@ -1,7 +1,8 @@
 #![cfg(test)]
 
 use super::*;
-use soroban_sdk::{testutils::{Address as _, Ledger}, Env};
+use ed25519_dalek::{Signer, SigningKey};
+use soroban_sdk::{testutils::{Address as _, Ledger}, vec, Env};
 
 #[test]
 fn test_initialize() {
@@ -32,8 +33,8 @@ fn test_store_and_retrieve_token() {
     client.initialize(&owner);
     
     // Simulate encrypted payload (in real scenario, encrypted with ring AES-GCM)
-    let encrypted_payload = Bytes::from_slice(&env, &[1, 2, 3, 4, 5, 6, 7, 8]);
-    let token_hash = BytesN::from_array(&env, &[0u8; 32]);
+    let encrypted_payload = Bytes::from_slice(&env, &[8u8; 40]);
+    let token_hash = BytesN::from_array(&env, &[1u8; 32]);
     let last_4_digits = String::from_str(&env, "1234");
     let card_network = String::from_str(&env, "visa");
     
@@ -47,16 +48,18 @@ fn test_store_and_retrieve_token() {
         &last_4_digits,
         &card_network,
         &expires_at,
+        &None,
     );
     
     assert_eq!(metadata.user, user);
     assert_eq!(metadata.last_4_digits, last_4_digits);
     assert_eq!(metadata.card_network, card_network);
     assert_eq!(metadata.status, String::from_str(&env, "active"));
+    assert_eq!(metadata.issuer, None);
     assert_eq!(client.get_token_count(), 1);
     
     // Retrieve token
-    let retrieved = client.retrieve_token(&user).unwrap();
+    let retrieved = client.retrieve_token(&user, &token_hash).unwrap();
     assert_eq!(retrieved.encrypted_payload, encrypted_payload);
     assert_eq!(retrieved.token_hash, token_hash);
 }
@@ -75,17 +78,17 @@ fn test_store_duplicate_token() {
     
     client.initialize(&owner);
     
-    let encrypted_payload = Bytes::from_slice(&env, &[1, 2, 3, 4]);
-    let token_hash = BytesN::from_array(&env, &[0u8; 32]);
+    let encrypted_payload = Bytes::from_slice(&env, &[4u8; 32]);
+    let token_hash = BytesN::from_array(&env, &[1u8; 32]);
     let last_4_digits = String::from_str(&env, "1234");
     let card_network = String::from_str(&env, "visa");
     let expires_at = env.ledger().timestamp() + 31536000;
     
     // Store first token
-    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at);
+    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &None);
     
     // Attempt to store duplicate - should panic
-    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at);
+    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &None);
 }
 
 #[test]
@@ -101,19 +104,22 @@ fn test_revoke_token() {
     
     client.initialize(&owner);
     
-    let encrypted_payload = Bytes::from_slice(&env, &[1, 2, 3, 4]);
-    let token_hash = BytesN::from_array(&env, &[0u8; 32]);
+    let encrypted_payload = Bytes::from_slice(&env, &[4u8; 32]);
+    let token_hash = BytesN::from_array(&env, &[1u8; 32]);
     let last_4_digits = String::from_str(&env, "1234");
     let card_network = String::from_str(&env, "mastercard");
     let expires_at = env.ledger().timestamp() + 31536000;
     
-    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at);
+    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &None);
     
     // Revoke token
-    let revoked = client.revoke_token(&user);
+    let revoked = client.revoke_token(&user, &token_hash);
     assert!(revoked);
     
-    let status = client.get_token_status(&user).unwrap();
+    // Revoking again is a no-op
+    assert!(!client.revoke_token(&user, &token_hash));
+    
+    let status = client.get_token_status(&user, &token_hash).unwrap();
     assert_eq!(status, String::from_str(&env, "revoked"));
 }
 
@@ -130,15 +136,15 @@ fn test_expired_token() {
     
     client.initialize(&owner);
     
-    let encrypted_payload = Bytes::from_slice(&env, &[1, 2, 3, 4]);
-    let token_hash = BytesN::from_array(&env, &[0u8; 32]);
+    let encrypted_payload = Bytes::from_slice(&env, &[4u8; 32]);
+    let token_hash = BytesN::from_array(&env, &[1u8; 32]);
     let last_4_digits = String::from_str(&env, "5678");
     let card_network = String::from_str(&env, "rupay");
     
     // Set expiration to 1 second from now
     let expires_at = env.ledger().timestamp() + 1;
     
-    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at);
+    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &None);
     
     // Fast forward time by 2 seconds
     env.ledger().with_mut(|li| {
@@ -146,7 +152,7 @@ fn test_expired_token() {
     });
     
     // Retrieve should return expired token
-    let retrieved = client.retrieve_token(&user).unwrap();
+    let retrieved = client.retrieve_token(&user, &token_hash).unwrap();
     assert_eq!(retrieved.status, String::from_str(&env, "expired"));
 }
 
@@ -167,8 +173,139 @@ fn test_pause_unpause() {
     client.pause();
     assert!(client.is_paused());
     
-    client.unpause();
+    // Unpause is queued behind the timelock
+    let op_id = client.unpause();
+    assert!(client.is_paused());
+    
+    let delay = client.get_timelock_delay();
+    env.ledger().with_mut(|li| {
+        li.timestamp += delay;
+    });
+    
+    client.execute_operation(&op_id);
     assert!(!client.is_paused());
+    assert!(client.get_operation(&op_id).is_none());
+}
+
+#[test]
+#[should_panic(expected = "Timelock not expired")]
+fn test_execute_before_eta() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    let user = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    let op_id = client.update_permissions(&user, &Permission::Revoked);
+    
+    let delay = client.get_timelock_delay();
+    env.ledger().with_mut(|li| {
+        li.timestamp += delay - 1;
+    });
+    
+    client.execute_operation(&op_id);
+}
+
+#[test]
+#[should_panic(expected = "Operation expired")]
+fn test_execute_after_grace_period() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    let user = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    let op_id = client.update_permissions(&user, &Permission::Revoked);
+    let op = client.get_operation(&op_id).unwrap();
+    
+    env.ledger().with_mut(|li| {
+        li.timestamp = op.eta + GRACE_PERIOD + 1;
+    });
+    
+    client.execute_operation(&op_id);
+}
+
+#[test]
+#[should_panic(expected = "Timelock delay too short")]
+fn test_timelock_delay_minimum() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    client.initialize(&Address::generate(&env));
+    
+    client.set_timelock_delay(&0);
+}
+
+#[test]
+fn test_timelocked_permission_update() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    let user = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    let encrypted_payload = Bytes::from_slice(&env, &[4u8; 32]);
+    let token_hash = BytesN::from_array(&env, &[1u8; 32]);
+    let last_4_digits = String::from_str(&env, "4321");
+    let card_network = String::from_str(&env, "visa");
+    let expires_at = env.ledger().timestamp() + 31536000;
+    
+    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &None);
+    
+    let op_id = client.update_permissions(&user, &Permission::Revoked);
+    let op = client.get_operation(&op_id).unwrap();
+    assert_eq!(op.action, GovernanceAction::UpdatePermissions(user.clone(), Permission::Revoked));
+    assert_eq!(op.eta, op.queued_at + client.get_timelock_delay());
+    
+    // Queued change has no effect yet
+    assert!(client.retrieve_token(&user, &token_hash).is_some());
+    
+    env.ledger().with_mut(|li| {
+        li.timestamp = op.eta;
+    });
+    client.execute_operation(&op_id);
+    assert!(client.retrieve_token(&user, &token_hash).is_none());
+}
+
+#[test]
+#[should_panic(expected = "Operation not found")]
+fn test_cancel_operation() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    let op_id = client.set_timelock_delay(&3600);
+    client.cancel_operation(&op_id);
+    assert!(client.get_operation(&op_id).is_none());
+    
+    env.ledger().with_mut(|li| {
+        li.timestamp += 172800;
+    });
+    client.execute_operation(&op_id);
 }
 
 #[test]
@@ -184,15 +321,347 @@ fn test_events() {
     
     client.initialize(&owner);
     
-    let encrypted_payload = Bytes::from_slice(&env, &[1, 2, 3, 4]);
-    let token_hash = BytesN::from_array(&env, &[0u8; 32]);
+    let encrypted_payload = Bytes::from_slice(&env, &[4u8; 32]);
+    let token_hash = BytesN::from_array(&env, &[1u8; 32]);
     let last_4_digits = String::from_str(&env, "9999");
     let card_network = String::from_str(&env, "visa");
     let expires_at = env.ledger().timestamp() + 31536000;
     
-    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at);
+    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &None);
     
     // Test completed successfully - token was stored
     // Events are emitted but we verify via successful operation
     assert_eq!(client.get_token_count(), 1);
 }
+
+#[test]
+fn test_stats() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    let encrypted_payload = Bytes::from_slice(&env, &[4u8; 32]);
+    let token_hash = BytesN::from_array(&env, &[1u8; 32]);
+    let last_4_digits = String::from_str(&env, "1234");
+    let visa = String::from_str(&env, "visa");
+    let rupay = String::from_str(&env, "rupay");
+    let now = env.ledger().timestamp();
+    
+    client.store_token(&alice, &encrypted_payload, &token_hash, &last_4_digits, &visa, &(now + 31536000), &None);
+    client.store_token(&bob, &encrypted_payload, &token_hash, &last_4_digits, &visa, &(now + 31536000), &None);
+    client.store_token(&carol, &encrypted_payload, &token_hash, &last_4_digits, &rupay, &(now + 10), &None);
+    
+    let stats = client.get_stats();
+    assert_eq!(stats.active, 3);
+    assert_eq!(stats.by_network.get(visa.clone()), Some(2));
+    assert_eq!(stats.by_network.get(rupay.clone()), Some(1));
+    
+    // Revoke and purge
+    client.revoke_token(&alice, &token_hash);
+    assert_eq!(client.get_stats().revoked, 1);
+    client.purge_token(&alice, &token_hash);
+    
+    // Anyone can record an expiry, once, without the token being read
+    assert!(!client.expire_token(&carol, &token_hash));
+    env.ledger().with_mut(|li| {
+        li.timestamp += 11;
+    });
+    assert!(client.expire_token(&carol, &token_hash));
+    assert!(!client.expire_token(&carol, &token_hash));
+    assert_eq!(client.retrieve_token(&carol, &token_hash).unwrap().status, String::from_str(&env, "expired"));
+    assert!(!client.expire_token(&alice, &token_hash));
+    
+    // Owner suspension
+    let op_id = client.update_permissions(&bob, &Permission::Revoked);
+    let delay = client.get_timelock_delay();
+    env.ledger().with_mut(|li| {
+        li.timestamp += delay;
+    });
+    client.execute_operation(&op_id);
+    assert_eq!(client.get_token_status(&bob, &token_hash), Some(String::from_str(&env, "suspended")));
+    
+    let stats = client.get_stats();
+    assert_eq!(stats.active, 0);
+    assert_eq!(stats.suspended, 1);
+    assert_eq!(stats.revoked, 0);
+    assert_eq!(stats.expired, 1);
+    assert_eq!(stats.purged, 1);
+    assert_eq!(stats.by_network.get(visa), Some(1));
+    assert_eq!(stats.by_network.get(rupay), Some(1));
+    
+    // Total counter is unaffected by transitions
+    assert_eq!(client.get_token_count(), 3);
+}
+
+#[test]
+#[should_panic(expected = "Only revoked or expired tokens can be purged")]
+fn test_purge_active_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    let user = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    let encrypted_payload = Bytes::from_slice(&env, &[4u8; 32]);
+    let token_hash = BytesN::from_array(&env, &[1u8; 32]);
+    let last_4_digits = String::from_str(&env, "1234");
+    let card_network = String::from_str(&env, "visa");
+    let expires_at = env.ledger().timestamp() + 31536000;
+    
+    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &None);
+    client.purge_token(&user, &token_hash);
+}
+
+#[test]
+fn test_store_tokens_and_revoke_all() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    let user = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    let expires_at = env.ledger().timestamp() + 31536000;
+    let visa = NewToken {
+        encrypted_payload: Bytes::from_slice(&env, &[4u8; 32]),
+        token_hash: BytesN::from_array(&env, &[1u8; 32]),
+        last_4_digits: String::from_str(&env, "1111"),
+        card_network: String::from_str(&env, "visa"),
+        expires_at,
+        attestation: TokenAttestation::Unattested,
+    };
+    let rupay = NewToken {
+        encrypted_payload: Bytes::from_slice(&env, &[5u8; 32]),
+        token_hash: BytesN::from_array(&env, &[2u8; 32]),
+        last_4_digits: String::from_str(&env, "2222"),
+        card_network: String::from_str(&env, "rupay"),
+        expires_at,
+        attestation: TokenAttestation::Unattested,
+    };
+    
+    let stored = client.store_tokens(&user, &vec![&env, visa.clone(), rupay.clone()]);
+    assert_eq!(stored.len(), 2);
+    assert_eq!(client.list_tokens(&user), vec![&env, visa.token_hash.clone(), rupay.token_hash.clone()]);
+    assert_eq!(client.get_stats().active, 2);
+    
+    client.revoke_token(&user, &visa.token_hash);
+    
+    let results = client.revoke_all(&user);
+    assert_eq!(results, vec![
+        &env,
+        RevokeResult { token_hash: visa.token_hash.clone(), revoked: false },
+        RevokeResult { token_hash: rupay.token_hash.clone(), revoked: true },
+    ]);
+    assert!(client.retrieve_token(&user, &rupay.token_hash).is_none());
+    assert_eq!(client.get_stats().revoked, 2);
+}
+
+#[test]
+fn test_store_tokens_is_atomic() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    let user = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    let valid = NewToken {
+        encrypted_payload: Bytes::from_slice(&env, &[4u8; 32]),
+        token_hash: BytesN::from_array(&env, &[1u8; 32]),
+        last_4_digits: String::from_str(&env, "1111"),
+        card_network: String::from_str(&env, "visa"),
+        expires_at: env.ledger().timestamp() + 31536000,
+        attestation: TokenAttestation::Unattested,
+    };
+    let mut expired = valid.clone();
+    expired.token_hash = BytesN::from_array(&env, &[2u8; 32]);
+    expired.expires_at = env.ledger().timestamp();
+    
+    assert!(client.try_store_tokens(&user, &vec![&env, valid.clone(), expired]).is_err());
+    
+    // Duplicate hashes inside one batch are rejected too
+    assert!(client.try_store_tokens(&user, &vec![&env, valid.clone(), valid]).is_err());
+    
+    assert!(client.list_tokens(&user).is_empty());
+    assert_eq!(client.get_token_count(), 0);
+}
+
+#[test]
+fn test_store_attested_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    let user = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    // Register the issuer through the timelock
+    let issuer_key = SigningKey::from_bytes(&[7u8; 32]);
+    let issuer = BytesN::from_array(&env, &issuer_key.verifying_key().to_bytes());
+    let op_id = client.register_issuer(&issuer);
+    let delay = client.get_timelock_delay();
+    env.ledger().with_mut(|li| {
+        li.timestamp += delay;
+    });
+    client.execute_operation(&op_id);
+    assert!(client.is_issuer(&issuer));
+    
+    let encrypted_payload = Bytes::from_slice(&env, &[4u8; 32]);
+    let token_hash = BytesN::from_array(&env, &[3u8; 32]);
+    let last_4_digits = String::from_str(&env, "4242");
+    let card_network = String::from_str(&env, "visa");
+    let expires_at = env.ledger().timestamp() + 31536000;
+    
+    let payload = AttestationPayload {
+        user: user.clone(),
+        vault: contract_id.clone(),
+        token_hash: token_hash.clone(),
+        last_4_digits: last_4_digits.clone(),
+        card_network: card_network.clone(),
+        expires_at,
+    };
+    let mut message = [0u8; 512];
+    let xdr = payload.to_xdr(&env);
+    xdr.copy_into_slice(&mut message[..xdr.len() as usize]);
+    let signature = issuer_key.sign(&message[..xdr.len() as usize]);
+    let attestation = Attestation {
+        issuer: issuer.clone(),
+        signature: BytesN::from_array(&env, &signature.to_bytes()),
+    };
+    
+    // Attestation does not cover a different last 4
+    let forged = String::from_str(&env, "0000");
+    assert!(client
+        .try_store_token(&user, &encrypted_payload, &token_hash, &forged, &card_network, &expires_at, &Some(attestation.clone()))
+        .is_err());
+    
+    // Attestation cannot be replayed for another user
+    let other_user = Address::generate(&env);
+    assert!(client
+        .try_store_token(&other_user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &Some(attestation.clone()))
+        .is_err());
+    
+    let metadata = client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &Some(attestation));
+    assert_eq!(metadata.issuer, Some(issuer.clone()));
+    assert!(client.is_token_verified(&user, &token_hash));
+    
+    // Removing a compromised issuer withdraws verification from the tokens it attested
+    client.remove_issuer(&issuer);
+    assert!(!client.is_token_verified(&user, &token_hash));
+    
+    let op_id = client.register_issuer(&issuer);
+    env.ledger().with_mut(|li| {
+        li.timestamp += delay;
+    });
+    client.execute_operation(&op_id);
+    assert!(client.is_token_verified(&user, &token_hash));
+    
+    client.revoke_token(&user, &token_hash);
+    assert!(!client.is_token_verified(&user, &token_hash));
+}
+
+#[test]
+#[should_panic(expected = "Unknown issuer")]
+fn test_store_token_unknown_issuer() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    let user = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    let encrypted_payload = Bytes::from_slice(&env, &[4u8; 32]);
+    let token_hash = BytesN::from_array(&env, &[3u8; 32]);
+    let last_4_digits = String::from_str(&env, "4242");
+    let card_network = String::from_str(&env, "visa");
+    let expires_at = env.ledger().timestamp() + 31536000;
+    let attestation = Attestation {
+        issuer: BytesN::from_array(&env, &[9u8; 32]),
+        signature: BytesN::from_array(&env, &[0u8; 64]),
+    };
+    
+    client.store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &Some(attestation));
+}
+
+#[test]
+fn test_store_token_validation() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let contract_id = env.register(TokenVault, ());
+    let client = TokenVaultClient::new(&env, &contract_id);
+    
+    let owner = Address::generate(&env);
+    let user = Address::generate(&env);
+    
+    client.initialize(&owner);
+    
+    let encrypted_payload = Bytes::from_slice(&env, &[4u8; 32]);
+    let token_hash = BytesN::from_array(&env, &[1u8; 32]);
+    let last_4_digits = String::from_str(&env, "1234");
+    let card_network = String::from_str(&env, "visa");
+    let expires_at = env.ledger().timestamp() + 31536000;
+    
+    for digits in ["abcd", "", "123", "4111111111111111"] {
+        let result = client.try_store_token(&user, &encrypted_payload, &token_hash, &String::from_str(&env, digits), &card_network, &expires_at, &None);
+        assert_eq!(result.err(), Some(Ok(VaultError::InvalidLastFourDigits.into())));
+    }
+    
+    let short_payload = Bytes::from_slice(&env, &[4u8; 27]);
+    let result = client.try_store_token(&user, &short_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &None);
+    assert_eq!(result.err(), Some(Ok(VaultError::PayloadTooShort.into())));
+    
+    let large_payload = Bytes::from_slice(&env, &[4u8; 1025]);
+    let result = client.try_store_token(&user, &large_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &None);
+    assert_eq!(result.err(), Some(Ok(VaultError::PayloadTooLarge.into())));
+    
+    let zero_hash = BytesN::from_array(&env, &[0u8; 32]);
+    let result = client.try_store_token(&user, &encrypted_payload, &zero_hash, &last_4_digits, &card_network, &expires_at, &None);
+    assert_eq!(result.err(), Some(Ok(VaultError::ZeroTokenHash.into())));
+    
+    let unknown_network = String::from_str(&env, "network-0001");
+    let result = client.try_store_token(&user, &encrypted_payload, &token_hash, &last_4_digits, &unknown_network, &expires_at, &None);
+    assert_eq!(result.err(), Some(Ok(VaultError::UnsupportedCardNetwork.into())));
+    
+    // Raising the maximum goes through the timelock
+    let op_id = client.set_max_payload_size(&2048);
+    let delay = client.get_timelock_delay();
+    env.ledger().with_mut(|li| {
+        li.timestamp += delay;
+    });
+    client.execute_operation(&op_id);
+    assert_eq!(client.get_max_payload_size(), 2048);
+    
+    let expires_at = env.ledger().timestamp() + 31536000;
+    client.store_token(&user, &large_payload, &token_hash, &last_4_digits, &card_network, &expires_at, &None);
+}
@@ -0,0 +1,210 @@
+#![no_std]
+use soroban_sdk::{
+    contract, contractclient, contracterror, contractimpl, contracttype, panic_with_error, symbol_short,
+    xdr::ToXdr, Address, BytesN, Env, IntoVal, Map, Symbol, Topics, Val, Vec,
+};
+
+/// Storage keys
+#[contracttype]
+pub enum DataKey {
+    Admin,                  // Factory administrator
+    WalletWasm,             // Wasm hash of the AccountAbstraction wallet
+    GasToken,               // Gas token handed to every new wallet
+    Wallet(Address),        // Owner -> deployed wallet
+    WalletOwner(Address),   // Wallet -> owner
+    WalletCount,            // Total wallets deployed
+}
+
+/// Wallet signer; mirrors the AccountAbstraction `Signer` type
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Signer {
+    Address(Address),
+    Passkey(BytesN<65>),           // Uncompressed secp256r1 public key
+}
+
+/// Multisig operation class; mirrors the AccountAbstraction `OperationClass` type
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OperationClass {
+    Read,
+    Standard,
+    Admin,
+}
+
+/// Call a session key may authorize; mirrors the AccountAbstraction `SessionScope` type
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SessionScope {
+    pub contract: Address,
+    pub function: Symbol,
+    pub arg_limits: Vec<ArgLimit>,
+}
+
+/// Upper bound on an i128 call argument; mirrors the AccountAbstraction `ArgLimit` type
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ArgLimit {
+    pub index: u32,
+    pub max: i128,
+}
+
+/// Session key registered on the new wallet
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WalletSessionKey {
+    pub public_key: BytesN<32>,         // ed25519 public key
+    pub duration: u64,                  // Seconds from deployment
+    pub permissions: Vec<SessionScope>,
+}
+
+/// Initial wallet configuration applied right after deployment
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WalletConfig {
+    pub signers: Map<Signer, u32>, // Signer -> weight; empty keeps the owner as sole signer
+    pub threshold: u32,            // Total weight required when signers are set
+    pub class_thresholds: Map<OperationClass, u32>, // Per-class threshold overrides (needs signers)
+    pub session_keys: Vec<WalletSessionKey>,
+    pub sponsor: Option<Address>,  // Gas sponsor; also authorizes the deployment
+}
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum FactoryError {
+    NotInitialized = 1,
+    WalletExists = 2,       // Owner already has a wallet
+}
+
+/// Subset of the AccountAbstraction interface used during wallet setup
+#[allow(dead_code)]
+#[contractclient(name = "WalletClient")]
+trait Wallet {
+    fn initialize_wallet(env: Env, owner: Address, gas_token: Address, fee_admin: Address);
+    fn add_session_key(env: Env, user: Address, session_key: BytesN<32>, duration: u64, permissions: Vec<SessionScope>);
+    fn set_sponsor(env: Env, user: Address, sponsor: Address);
+    fn setup_multisig(
+        env: Env,
+        user: Address,
+        signers: Map<Signer, u32>,
+        threshold: u32,
+        class_thresholds: Map<OperationClass, u32>,
+    );
+}
+
+#[contract]
+pub struct WalletFactory;
+
+#[contractimpl]
+impl WalletFactory {
+    /// Initialize the factory with the wallet wasm hash and gas token
+    pub fn initialize(env: Env, admin: Address, wallet_wasm: BytesN<32>, gas_token: Address) {
+        if env.storage().instance().has(&DataKey::Admin) {
+            panic!("Already initialized");
+        }
+
+        admin.require_auth();
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage().instance().set(&DataKey::WalletWasm, &wallet_wasm);
+        env.storage().instance().set(&DataKey::GasToken, &gas_token);
+        env.storage().instance().set(&DataKey::WalletCount, &0u32);
+    }
+
+    /// Point new deployments at an upgraded wallet wasm; existing wallets are unaffected
+    pub fn set_wallet_wasm(env: Env, wallet_wasm: BytesN<32>) {
+        Self::admin(&env).require_auth();
+        env.storage().instance().set(&DataKey::WalletWasm, &wallet_wasm);
+
+        publish(&env, (symbol_short!("wasm_set"),), wallet_wasm);
+    }
+
+    /// Deploy, configure and register the owner's wallet
+    pub fn create_wallet(env: Env, owner: Address, config: WalletConfig) -> Address {
+        owner.require_auth();
+
+        if env.storage().persistent().has(&DataKey::Wallet(owner.clone())) {
+            panic_with_error!(&env, FactoryError::WalletExists);
+        }
+
+        let wallet_wasm: BytesN<32> = env.storage().instance().get(&DataKey::WalletWasm)
+            .unwrap_or_else(|| panic_with_error!(&env, FactoryError::NotInitialized));
+        let gas_token: Address = env.storage().instance().get(&DataKey::GasToken).unwrap();
+
+        let wallet = env.deployer()
+            .with_current_contract(Self::wallet_salt(&env, &owner))
+            .deploy_v2(wallet_wasm, ());
+
+        // The wallet authorizes its own configuration through the owner's signature,
+        // so multisig goes last: once active, the owner key alone no longer signs
+        let client = WalletClient::new(&env, &wallet);
+        // The factory admin, not the owner, sets the wallet's fees at its sponsors' expense
+        client.initialize_wallet(&owner, &gas_token, &Self::admin(&env));
+        for session_key in config.session_keys.iter() {
+            client.add_session_key(&wallet, &session_key.public_key, &session_key.duration, &session_key.permissions);
+        }
+        if let Some(sponsor) = config.sponsor {
+            client.set_sponsor(&wallet, &sponsor);
+        }
+        if !config.signers.is_empty() {
+            client.setup_multisig(&wallet, &config.signers, &config.threshold, &config.class_thresholds);
+        }
+
+        env.storage().persistent().set(&DataKey::Wallet(owner.clone()), &wallet);
+        env.storage().persistent().set(&DataKey::WalletOwner(wallet.clone()), &owner);
+        let count: u32 = env.storage().instance().get(&DataKey::WalletCount).unwrap_or(0);
+        env.storage().instance().set(&DataKey::WalletCount, &(count + 1));
+
+        publish(&env, (symbol_short!("wallet"), owner), wallet.clone());
+
+        wallet
+    }
+
+    /// Address the owner's wallet has, or will have once deployed
+    pub fn predict_wallet(env: Env, owner: Address) -> Address {
+        env.deployer()
+            .with_current_contract(Self::wallet_salt(&env, &owner))
+            .deployed_address()
+    }
+
+    /// Deployed wallet of an owner
+    pub fn get_wallet(env: Env, owner: Address) -> Option<Address> {
+        env.storage().persistent().get(&DataKey::Wallet(owner))
+    }
+
+    /// Owner a wallet was deployed for
+    pub fn get_wallet_owner(env: Env, wallet: Address) -> Option<Address> {
+        env.storage().persistent().get(&DataKey::WalletOwner(wallet))
+    }
+
+    /// Wasm hash used for new wallets
+    pub fn get_wallet_wasm(env: Env) -> BytesN<32> {
+        env.storage().instance().get(&DataKey::WalletWasm)
+            .unwrap_or_else(|| panic_with_error!(&env, FactoryError::NotInitialized))
+    }
+
+    /// Total wallets deployed by the factory
+    pub fn get_wallet_count(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::WalletCount).unwrap_or(0)
+    }
+
+    // Helper functions
+
+    fn admin(env: &Env) -> Address {
+        env.storage().instance().get(&DataKey::Admin)
+            .unwrap_or_else(|| panic_with_error!(env, FactoryError::NotInitialized))
+    }
+
+    /// Deployment salt: sha256 of the owner's XDR encoding
+    fn wallet_salt(env: &Env, owner: &Address) -> BytesN<32> {
+        env.crypto().sha256(&owner.clone().to_xdr(env)).into()
+    }
+}
+
+/// Publish a (topics, data) event; the one place the deprecated untyped event API is used
+#[allow(deprecated)]
+fn publish<T: Topics, D: IntoVal<Env, Val>>(env: &Env, topics: T, data: D) {
+    env.events().publish(topics, data);
+}
+
+mod test;
@@ -0,0 +1,205 @@
+#![cfg(test)]
+extern crate std;
+
+use super::*;
+use account_abstraction::{AAError, AccountAbstraction, AccountAbstractionClient};
+use soroban_env_host::storage::AccessType;
+use soroban_sdk::{
+    map,
+    testutils::{Address as _, AuthorizedFunction},
+    vec,
+    xdr::{ContractDataDurability, LedgerKey, LedgerKeyContractData, ScVal},
+    Bytes, Env,
+};
+use std::rc::Rc;
+
+fn setup(env: &Env) -> (Address, WalletFactoryClient<'_>, Address) {
+    // The new wallet and its sponsor authorize calls nested under create_wallet
+    env.mock_all_auths_allowing_non_root_auth();
+    let contract_id = env.register(WalletFactory, ());
+    let client = WalletFactoryClient::new(env, &contract_id);
+
+    let admin = Address::generate(env);
+    let gas_token = Address::generate(env);
+    client.initialize(&admin, &BytesN::from_array(env, &[7; 32]), &gas_token);
+
+    (contract_id, client, admin)
+}
+
+fn empty_config(env: &Env) -> WalletConfig {
+    WalletConfig {
+        signers: Map::new(env),
+        threshold: 0,
+        class_thresholds: Map::new(env),
+        session_keys: Vec::new(env),
+        sponsor: None,
+    }
+}
+
+/// Stand the native AccountAbstraction in for the wallet wasm at the owner's predicted address.
+/// The test host runs contracts whose executable is the empty wasm through their native
+/// registration, so the instance `register_at` creates is dropped for the factory to deploy anew
+fn register_wallet_contract(env: &Env, client: &WalletFactoryClient, owner: &Address) {
+    let wallet = client.predict_wallet(owner);
+    env.register_at(&wallet, AccountAbstraction, ());
+
+    let instance_key = LedgerKey::ContractData(LedgerKeyContractData {
+        contract: wallet.into(),
+        key: ScVal::LedgerKeyContractInstance,
+        durability: ContractDataDurability::Persistent,
+    });
+    env.host().setup_storage_entry(Rc::new(instance_key), None, AccessType::ReadWrite).unwrap();
+
+    client.set_wallet_wasm(&env.crypto().sha256(&Bytes::new(env)).into());
+}
+
+#[test]
+fn test_predict_wallet_is_deterministic_per_owner() {
+    let env = Env::default();
+    let (contract_id, client, _) = setup(&env);
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+
+    let predicted = client.predict_wallet(&alice);
+    assert_eq!(client.predict_wallet(&alice), predicted);
+    assert_ne!(client.predict_wallet(&bob), predicted);
+
+    // Anyone can derive the same address off-chain from the factory and owner
+    let salt: BytesN<32> = env.crypto().sha256(&alice.clone().to_xdr(&env)).into();
+    assert_eq!(
+        env.deployer().with_address(contract_id, salt).deployed_address(),
+        predicted
+    );
+
+    // Nothing is registered until the wallet is deployed
+    assert_eq!(client.get_wallet(&alice), None);
+    assert_eq!(client.get_wallet_owner(&predicted), None);
+    assert_eq!(client.get_wallet_count(), 0);
+}
+
+#[test]
+fn test_create_wallet_requires_uploaded_wasm() {
+    let env = Env::default();
+    let (_, client, _) = setup(&env);
+
+    let owner = Address::generate(&env);
+    let config = empty_config(&env);
+
+    // The configured hash was never uploaded, so deployment fails and nothing is recorded
+    assert!(client.try_create_wallet(&owner, &config).is_err());
+    assert_eq!(client.get_wallet(&owner), None);
+    assert_eq!(client.get_wallet_count(), 0);
+}
+
+#[test]
+fn test_set_wallet_wasm_requires_admin() {
+    let env = Env::default();
+    let (_, client, admin) = setup(&env);
+
+    let new_wasm = BytesN::from_array(&env, &[9; 32]);
+    client.set_wallet_wasm(&new_wasm);
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(client.get_wallet_wasm(), new_wasm);
+}
+
+#[test]
+#[should_panic(expected = "Already initialized")]
+fn test_initialize_twice() {
+    let env = Env::default();
+    let (_, client, _) = setup(&env);
+
+    client.initialize(&Address::generate(&env), &BytesN::from_array(&env, &[7; 32]), &Address::generate(&env));
+}
+
+#[test]
+fn test_create_wallet_deploys_at_predicted_address() {
+    let env = Env::default();
+    let (_, client, admin) = setup(&env);
+
+    let owner = Address::generate(&env);
+    let predicted = client.predict_wallet(&owner);
+    register_wallet_contract(&env, &client, &owner);
+
+    let wallet = client.create_wallet(&owner, &empty_config(&env));
+    assert_eq!(wallet, predicted);
+    assert_eq!(client.get_wallet(&owner), Some(wallet.clone()));
+    assert_eq!(client.get_wallet_owner(&wallet), Some(owner.clone()));
+    assert_eq!(client.get_wallet_count(), 1);
+
+    // The wallet is initialized for the owner as a per-user wallet
+    let wallet_client = AccountAbstractionClient::new(&env, &wallet);
+    assert!(wallet_client.is_wallet());
+    assert_eq!(wallet_client.get_multisig(&wallet), None);
+    assert_eq!(
+        wallet_client.try_initialize(&owner, &Address::generate(&env)).err(),
+        Some(Ok(AAError::AlreadyInitialized))
+    );
+
+    // Its fees are set by the factory admin rather than the owner
+    let mut schedule = wallet_client.get_fee_schedule();
+    schedule.base_fee = 100000;
+    wallet_client.set_fee_schedule(&schedule);
+    assert_eq!(env.auths()[0].0, admin);
+
+    // Nobody else can keep account state in it
+    let stranger = Address::generate(&env);
+    assert_eq!(
+        wallet_client.try_set_sponsor(&stranger, &Address::generate(&env)).err(),
+        Some(Ok(AAError::NotOwnAccount))
+    );
+
+    // One wallet per owner
+    assert_eq!(
+        client.try_create_wallet(&owner, &empty_config(&env)).err(),
+        Some(Ok(FactoryError::WalletExists.into()))
+    );
+    assert_eq!(client.get_wallet_count(), 1);
+}
+
+#[test]
+fn test_create_wallet_applies_config() {
+    let env = Env::default();
+    let (_, client, _) = setup(&env);
+
+    let owner = Address::generate(&env);
+    let cosigner = Address::generate(&env);
+    let sponsor = Address::generate(&env);
+    let session_key = BytesN::from_array(&env, &[3u8; 32]);
+    register_wallet_contract(&env, &client, &owner);
+
+    let config = WalletConfig {
+        signers: map![&env, (Signer::Address(owner.clone()), 1), (Signer::Address(cosigner.clone()), 1)],
+        threshold: 2,
+        class_thresholds: map![&env, (OperationClass::Read, 1)],
+        session_keys: vec![&env, WalletSessionKey { public_key: session_key.clone(), duration: 3600, permissions: Vec::new(&env) }],
+        sponsor: Some(sponsor.clone()),
+    };
+    let wallet = client.create_wallet(&owner, &config);
+
+    // The wallet authorized its own setup; the sponsor agreed to sponsor it
+    let authorized = |address: &Address, function: &str| {
+        env.auths().iter().any(|(signer, invocation)| {
+            signer == address && matches!(
+                &invocation.function,
+                AuthorizedFunction::Contract((contract, name, _)) if *contract == wallet && *name == Symbol::new(&env, function)
+            )
+        })
+    };
+    assert!(authorized(&wallet, "setup_multisig"));
+    assert!(authorized(&wallet, "set_sponsor"));
+    assert!(authorized(&wallet, "add_session_key"));
+    assert!(authorized(&sponsor, "set_sponsor"));
+
+    let wallet_client = AccountAbstractionClient::new(&env, &wallet);
+    let (signers, threshold) = wallet_client.get_multisig(&wallet).unwrap();
+    assert_eq!(signers.len(), 2);
+    assert_eq!(threshold, 2);
+    assert_eq!(wallet_client.get_class_threshold(&wallet, &account_abstraction::OperationClass::Read), Some(1));
+    assert_eq!(wallet_client.get_class_threshold(&wallet, &account_abstraction::OperationClass::Admin), Some(2));
+    assert_eq!(wallet_client.list_session_keys(&wallet).get(0).unwrap().key, session_key);
+    assert_eq!(wallet_client.get_sponsor_allowance(&wallet).unwrap().sponsor, sponsor);
+
+    let features = wallet_client.get_features(&wallet);
+    assert!(features.multisig && features.session_keys && features.sponsored);
+}
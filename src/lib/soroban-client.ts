@@ -64,6 +64,7 @@ export async function buildStoreTokenTransaction(
     const last4 = nativeToScVal(params.last4Digits, { type: "string" });
     const network = nativeToScVal(params.network, { type: "string" });
     const expiresAtVal = nativeToScVal(params.expiresAt, { type: "u64" });
+    const attestation = nativeToScVal(null); // No issuer attestation
 
     // Build transaction
     const transaction = new TransactionBuilder(account, {
@@ -78,7 +79,8 @@ export async function buildStoreTokenTransaction(
                 hashBytes,
                 last4,
                 network,
-                expiresAtVal
+                expiresAtVal,
+                attestation
             )
         )
         .setTimeout(30)